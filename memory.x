@@ -1,7 +1,8 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 512K 
+  /* The last 128K sector (7) is left out for stored settings, see config_store.rs */
+  FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 384K
   RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 128K
 }
 
@@ -0,0 +1,206 @@
+use smart_leds::RGB8;
+
+use crate::strip::MAX_LEDS;
+
+/// Sent to the host on start up, as Adalight devices do
+pub const HELLO: &[u8] = b"Ada\n";
+
+const MAGIC: &[u8; 3] = b"Ada";
+
+/// Colors of one frame received from the host
+#[derive(Clone, Copy)]
+pub struct HostFrame {
+    pub leds: [RGB8; MAX_LEDS],
+    /// LEDs received, at most `MAX_LEDS`; colors for LEDs past that are
+    /// dropped
+    pub len: usize,
+}
+
+impl HostFrame {
+    pub fn leds(&self) -> &[RGB8] {
+        &self.leds[..self.len]
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Magic(usize), // Header bytes matched so far
+    CountHigh,
+    CountLow(u8),
+    Checksum(u8, u8),
+    Data { byte_qty: usize, received: usize },
+}
+
+/// Adalight stream parser
+///
+/// A frame is "Ada", the LED count minus one as a big endian u16, that
+/// count's high byte ^ low byte ^ 0x55, then R, G, B for every LED. A header
+/// with a bad checksum is skipped and the parser looks for the next "Ada",
+/// so it falls back in step after a lost byte.
+pub struct Parser {
+    state: State,
+    frame: HostFrame,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Magic(0),
+            frame: HostFrame {
+                leds: [RGB8 { r: 0, g: 0, b: 0 }; MAX_LEDS],
+                len: 0,
+            },
+        }
+    }
+
+    /// Drop any partly received frame, e.g. after a UART error
+    pub fn reset(&mut self) {
+        self.state = State::Magic(0);
+    }
+
+    /// Whether the parser is between frames, looking for a header
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, State::Magic(0))
+    }
+
+    /// Take in one byte; returns the frame it completes, if any
+    pub fn feed(&mut self, byte: u8) -> Option<&HostFrame> {
+        self.state = match self.state {
+            State::Magic(matched) if byte == MAGIC[matched] => match matched + 1 {
+                3 => State::CountHigh,
+                matched => State::Magic(matched),
+            },
+            // An 'A' in the wrong place may still start a header
+            State::Magic(_) => State::Magic(usize::from(byte == MAGIC[0])),
+            State::CountHigh => State::CountLow(byte),
+            State::CountLow(high) => State::Checksum(high, byte),
+            State::Checksum(high, low) if byte == high ^ low ^ 0x55 => {
+                let led_qty = usize::from(u16::from_be_bytes([high, low])) + 1;
+                self.frame.len = led_qty.min(MAX_LEDS);
+                State::Data {
+                    byte_qty: led_qty * 3,
+                    received: 0,
+                }
+            }
+            State::Checksum(..) => State::Magic(usize::from(byte == MAGIC[0])),
+            State::Data { byte_qty, received } => {
+                if let Some(led) = self.frame.leds.get_mut(received / 3) {
+                    match received % 3 {
+                        0 => led.r = byte,
+                        1 => led.g = byte,
+                        _ => led.b = byte,
+                    }
+                }
+
+                if received + 1 == byte_qty {
+                    self.state = State::Magic(0);
+                    return Some(&self.frame);
+                }
+                State::Data {
+                    byte_qty,
+                    received: received + 1,
+                }
+            }
+        };
+
+        None
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Header for `led_qty` LEDs
+    fn header(led_qty: u16) -> [u8; 6] {
+        let [high, low] = (led_qty - 1).to_be_bytes();
+        [b'A', b'd', b'a', high, low, high ^ low ^ 0x55]
+    }
+
+    /// Feed `bytes`, returning the LEDs of every frame completed
+    fn feed_all(parser: &mut Parser, bytes: &[u8]) -> Vec<Vec<RGB8>> {
+        bytes
+            .iter()
+            .filter_map(|&byte| parser.feed(byte).map(|frame| frame.leds().to_vec()))
+            .collect()
+    }
+
+    fn frame_bytes(colors: &[[u8; 3]]) -> Vec<u8> {
+        let mut bytes = header(colors.len() as u16).to_vec();
+        bytes.extend(colors.iter().flatten());
+        bytes
+    }
+
+    #[test]
+    fn parses_a_frame() {
+        let mut parser = Parser::new();
+        let frames = feed_all(&mut parser, &frame_bytes(&[[1, 2, 3], [4, 5, 6]]));
+
+        assert_eq!(frames, [vec![RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)]]);
+        assert!(parser.is_idle());
+    }
+
+    #[test]
+    fn skips_noise_before_the_header() {
+        let mut parser = Parser::new();
+        let mut bytes = b"xxAAdAd".to_vec();
+        bytes.extend(frame_bytes(&[[7, 8, 9]]));
+
+        assert_eq!(feed_all(&mut parser, &bytes), [vec![RGB8::new(7, 8, 9)]]);
+    }
+
+    #[test]
+    fn bad_checksum_resyncs_on_the_next_header() {
+        let mut parser = Parser::new();
+        let mut bytes = header(1).to_vec();
+        bytes[5] ^= 1;
+        bytes.extend([10, 20, 30]); // Data of the rejected header, taken as noise
+        bytes.extend(frame_bytes(&[[1, 1, 1]]));
+
+        assert_eq!(feed_all(&mut parser, &bytes), [vec![RGB8::new(1, 1, 1)]]);
+    }
+
+    #[test]
+    fn checksum_byte_may_start_the_next_header() {
+        let mut parser = Parser::new();
+        let mut bytes = header(1)[..5].to_vec();
+        bytes.extend(frame_bytes(&[[2, 2, 2]])); // Its 'A' is read as a bad checksum
+
+        assert_eq!(feed_all(&mut parser, &bytes), [vec![RGB8::new(2, 2, 2)]]);
+    }
+
+    #[test]
+    fn long_frames_are_cut_to_max_leds() {
+        let mut parser = Parser::new();
+        let colors: Vec<[u8; 3]> = (0..MAX_LEDS as u8 + 4).map(|i| [i, i, i]).collect();
+        let mut bytes = frame_bytes(&colors);
+        bytes.extend(frame_bytes(&[[9, 9, 9]]));
+
+        let frames = feed_all(&mut parser, &bytes);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].len(), MAX_LEDS);
+        assert_eq!(frames[0][MAX_LEDS - 1], RGB8::new(31, 31, 31));
+        assert_eq!(frames[1], [RGB8::new(9, 9, 9)]);
+    }
+
+    #[test]
+    fn reset_drops_a_partial_frame() {
+        let mut parser = Parser::new();
+        let bytes = frame_bytes(&[[1, 2, 3], [4, 5, 6]]);
+        feed_all(&mut parser, &bytes[..8]);
+        assert!(!parser.is_idle());
+
+        parser.reset();
+        assert!(parser.is_idle());
+        assert_eq!(
+            feed_all(&mut parser, &bytes),
+            [vec![RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)]]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+/// Light reading to brightness, linear between points
+///
+/// Points are (reading, brightness) pairs sorted by reading; readings
+/// outside of them take the brightness of the nearest end. An empty curve
+/// is full brightness throughout.
+#[derive(Clone, Copy)]
+pub struct BrightnessCurve {
+    points: &'static [(u16, u8)],
+}
+
+impl BrightnessCurve {
+    pub const fn new(points: &'static [(u16, u8)]) -> Self {
+        Self { points }
+    }
+
+    pub fn brightness(&self, reading: u16) -> u8 {
+        let Some(&(first_reading, first_brightness)) = self.points.first() else {
+            return 255;
+        };
+        if reading <= first_reading {
+            return first_brightness;
+        }
+
+        for pair in self.points.windows(2) {
+            let [(low, low_brightness), (high, high_brightness)] = [pair[0], pair[1]];
+            if reading <= high {
+                let span = i32::from(high - low).max(1);
+                let step = i32::from(high_brightness) - i32::from(low_brightness);
+                let offset = i32::from(reading - low) * step / span;
+                return (i32::from(low_brightness) + offset) as u8;
+            }
+        }
+
+        self.points[self.points.len() - 1].1
+    }
+}
+
+/// Light sensor readings smoothed over several seconds
+///
+/// A hand passing over the sensor or a lamp being switched on should not
+/// make the LEDs jump, so each reading only moves the level by 1/2^`shift`
+/// of the way there. The first reading is taken as is.
+pub struct AmbientLight {
+    shift: u32,
+    level: Option<u32>, // Q8, None before the first reading
+}
+
+impl AmbientLight {
+    /// Smoothing over about 2^`shift` readings
+    pub const fn new(shift: u32) -> Self {
+        Self { shift, level: None }
+    }
+
+    /// Take in a reading; returns the smoothed level
+    pub fn update(&mut self, reading: u16) -> u16 {
+        let target = i64::from(reading) << 8;
+        let level = match self.level {
+            Some(level) => {
+                // Rounded away from the level, so it always gets all the way
+                let difference = target - i64::from(level);
+                let step = (difference.abs() + (1 << self.shift) - 1) >> self.shift;
+                i64::from(level) + step * difference.signum()
+            }
+            None => target,
+        };
+        self.level = Some(level as u32);
+
+        self.level()
+    }
+
+    /// Smoothed level, 0 before the first reading
+    pub fn level(&self) -> u16 {
+        self.level.map_or(0, |level| ((level + 128) >> 8) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVE: BrightnessCurve = BrightnessCurve::new(&[(100, 16), (1000, 96), (3000, 255)]);
+
+    #[test]
+    fn curve_holds_its_ends() {
+        assert_eq!(CURVE.brightness(0), 16);
+        assert_eq!(CURVE.brightness(100), 16);
+        assert_eq!(CURVE.brightness(3000), 255);
+        assert_eq!(CURVE.brightness(u16::MAX), 255);
+        assert_eq!(BrightnessCurve::new(&[]).brightness(1234), 255);
+        assert_eq!(BrightnessCurve::new(&[(500, 80)]).brightness(9000), 80);
+    }
+
+    #[test]
+    fn curve_is_linear_between_points() {
+        assert_eq!(CURVE.brightness(550), 56);
+        assert_eq!(CURVE.brightness(1000), 96);
+        assert_eq!(CURVE.brightness(2000), 175);
+        assert!(
+            (0..4000).all(|reading| CURVE.brightness(reading + 1) >= CURVE.brightness(reading))
+        );
+    }
+
+    #[test]
+    fn curve_may_fall() {
+        let inverted = BrightnessCurve::new(&[(0, 255), (1000, 0)]);
+        assert_eq!(inverted.brightness(500), 128);
+        assert_eq!(inverted.brightness(2000), 0);
+    }
+
+    #[test]
+    fn first_reading_is_taken_as_is() {
+        let mut ambient = AmbientLight::new(4);
+        assert_eq!(ambient.level(), 0);
+        assert_eq!(ambient.update(700), 700);
+    }
+
+    #[test]
+    fn smoothing_follows_slowly_but_all_the_way() {
+        let mut ambient = AmbientLight::new(4);
+        ambient.update(0);
+
+        // A lamp switched on moves the level by a sixteenth per reading
+        assert_eq!(ambient.update(1600), 100);
+        let levels: Vec<u16> = (0..200).map(|_| ambient.update(1600)).collect();
+        assert!(levels.windows(2).all(|pair| pair[1] >= pair[0]));
+        assert!(levels[10] < 1600);
+        assert_eq!(*levels.last().unwrap(), 1600);
+
+        // And back down, without sticking short of it
+        let level = (0..300).map(|_| ambient.update(3)).last().unwrap();
+        assert_eq!(level, 3);
+    }
+
+    #[test]
+    fn a_passing_shadow_barely_shows() {
+        let mut ambient = AmbientLight::new(4);
+        ambient.update(2000);
+        ambient.update(0);
+        let level = ambient.update(2000);
+        assert!(level > 1800, "{}", level);
+    }
+}
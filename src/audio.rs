@@ -0,0 +1,321 @@
+use crate::effects::sound::AudioLevels;
+
+/// Microphone sample rate the bands are laid out for
+pub const SAMPLE_RATE_HZ: u32 = 8000;
+/// Samples per analysis, giving 62.5 level updates per second and 62.5Hz
+/// FFT bins
+pub const BLOCK_LEN: usize = 128;
+
+/// One block of 12-bit ADC samples
+pub type SampleBlock = [u16; BLOCK_LEN];
+
+const LOG2_BLOCK_LEN: u32 = 7;
+// FFT bins of each band, by bin number times 62.5Hz
+const BASS_BINS: (usize, usize) = (1, 4); // 62-250Hz
+const MID_BINS: (usize, usize) = (5, 32); // 310Hz-2kHz
+const TREBLE_BINS: (usize, usize) = (33, 63); // 2-4kHz
+
+const NOISE_FLOOR: u32 = 6; // ADC counts of amplitude taken as silence
+const PEAK_FLOOR: u32 = 64; // Keeps quiet rooms from being turned up to full
+const PEAK_DECAY_SHIFT: u32 = 7; // Loudness tracking, ~2s
+const BEAT_AVERAGE_SHIFT: u32 = 4; // Bass average over ~16 blocks
+const MIN_BEAT_BLOCKS: u8 = 14; // ~220ms, above 270 BPM is not a beat
+
+/// sin(pi / 2 * i / 32) in Q15, a quarter wave of `BLOCK_LEN` steps
+const QUARTER_SINE: [i32; 33] = [
+    0, 1608, 3212, 4808, 6393, 7962, 9512, 11039, 12539, 14010, 15446, 16846, 18204, 19519, 20787,
+    22005, 23170, 24279, 25329, 26319, 27245, 28105, 28898, 29621, 30273, 30852, 31356, 31785,
+    32137, 32412, 32609, 32728, 32767,
+];
+
+/// Band levels and beats from blocks of microphone samples
+///
+/// Levels follow the loudness, so that quiet and loud music both use the
+/// whole range; beats are bass peaks well above the recent bass average.
+pub struct Analyzer {
+    peak: u32,
+    bass_average: u32,
+    blocks_since_beat: u8,
+    beats: u8,
+}
+
+impl Analyzer {
+    pub const fn new() -> Self {
+        Self {
+            peak: PEAK_FLOOR,
+            bass_average: 0,
+            blocks_since_beat: MIN_BEAT_BLOCKS,
+            beats: 0,
+        }
+    }
+
+    pub fn analyze(&mut self, block: &SampleBlock) -> AudioLevels {
+        let mut re = [0i32; BLOCK_LEN];
+        let mut im = [0i32; BLOCK_LEN];
+
+        // The block mean is the microphone bias
+        let mean = block.iter().map(|&sample| u32::from(sample)).sum::<u32>() / BLOCK_LEN as u32;
+        for (i, (re, &sample)) in re.iter_mut().zip(block).enumerate() {
+            // Hann window, sin^2(pi * i / N) = (1 - cos(2 * pi * i / N)) / 2
+            let window = (32768 - sin_q15(i + BLOCK_LEN / 4)) / 2;
+            *re = ((i32::from(sample) - mean as i32) * window) >> 15;
+        }
+        fft(&mut re, &mut im);
+
+        let band = |(first, last): (usize, usize)| {
+            let power: u64 = (first..=last)
+                .map(|bin| {
+                    let (re, im) = (i64::from(re[bin]), i64::from(im[bin]));
+                    (re * re + im * im) as u64
+                })
+                .sum();
+            // A sine of amplitude A gives |X| = N * A / 4 through the window
+            let amplitude = isqrt(power) * 4 / BLOCK_LEN as u32;
+            if amplitude < NOISE_FLOOR {
+                0
+            } else {
+                amplitude
+            }
+        };
+        let (bass, mid, treble) = (band(BASS_BINS), band(MID_BINS), band(TREBLE_BINS));
+
+        self.detect_beat(bass);
+        self.peak -= self.peak >> PEAK_DECAY_SHIFT;
+        self.peak = self.peak.max(bass).max(mid).max(treble).max(PEAK_FLOOR);
+        let level = |amplitude: u32| (amplitude * 255 / self.peak).min(255) as u8;
+
+        AudioLevels {
+            bass: level(bass),
+            mid: level(mid),
+            treble: level(treble),
+            beats: self.beats,
+        }
+    }
+
+    fn detect_beat(&mut self, bass: u32) {
+        self.blocks_since_beat = self.blocks_since_beat.saturating_add(1);
+        if bass > self.bass_average * 3 / 2
+            && bass >= NOISE_FLOOR * 4
+            && self.blocks_since_beat >= MIN_BEAT_BLOCKS
+        {
+            self.beats = self.beats.wrapping_add(1);
+            self.blocks_since_beat = 0;
+        }
+
+        // The shift rounds down, so silence takes the average all the way to 0
+        let average = self.bass_average as i32;
+        let step = (bass as i32 - average) >> BEAT_AVERAGE_SHIFT;
+        self.bass_average = (average + step).max(0) as u32;
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// sin(2 * pi * `step` / `BLOCK_LEN`) in Q15
+fn sin_q15(step: usize) -> i32 {
+    let quarter = BLOCK_LEN / 4;
+    let step = step % BLOCK_LEN;
+
+    match step / quarter {
+        0 => QUARTER_SINE[step],
+        1 => QUARTER_SINE[2 * quarter - step],
+        2 => -QUARTER_SINE[step - 2 * quarter],
+        _ => -QUARTER_SINE[BLOCK_LEN - step],
+    }
+}
+
+/// In-place radix-2 FFT of `BLOCK_LEN` points
+///
+/// Unscaled: 12-bit input grows by at most `BLOCK_LEN`, well within i32.
+fn fft(re: &mut [i32; BLOCK_LEN], im: &mut [i32; BLOCK_LEN]) {
+    for i in 0..BLOCK_LEN {
+        let j = i.reverse_bits() >> (usize::BITS - LOG2_BLOCK_LEN);
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut half = 1;
+    while half < BLOCK_LEN {
+        let twiddle_step = BLOCK_LEN / (half * 2);
+        for start in (0..BLOCK_LEN).step_by(half * 2) {
+            for k in 0..half {
+                // e^(-2 * pi * i * k / (2 * half))
+                let angle = k * twiddle_step;
+                let (cos, sin) = (
+                    i64::from(sin_q15(angle + BLOCK_LEN / 4)),
+                    -i64::from(sin_q15(angle)),
+                );
+                let (a, b) = (start + k, start + k + half);
+                let (b_re, b_im) = (i64::from(re[b]), i64::from(im[b]));
+                let t_re = ((b_re * cos - b_im * sin) >> 15) as i32;
+                let t_im = ((b_re * sin + b_im * cos) >> 15) as i32;
+
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        half *= 2;
+    }
+}
+
+fn isqrt(value: u64) -> u32 {
+    let mut result = 0u64;
+    let mut bit = 1u64 << 62;
+    let mut value = value;
+
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if value >= result + bit {
+            value -= result + bit;
+            result = (result >> 1) + bit;
+        } else {
+            result >>= 1;
+        }
+        bit >>= 2;
+    }
+
+    result as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BIAS: f64 = 2048.0;
+
+    /// `amplitude` counts of `frequency_hz` about the microphone bias,
+    /// starting `offset` samples in
+    fn tone(frequency_hz: f64, amplitude: f64, offset: usize) -> SampleBlock {
+        let mut block = [0u16; BLOCK_LEN];
+        for (i, sample) in block.iter_mut().enumerate() {
+            let t = (offset + i) as f64 / f64::from(SAMPLE_RATE_HZ);
+            *sample = (BIAS + amplitude * (2.0 * core::f64::consts::PI * frequency_hz * t).sin())
+                .round() as u16;
+        }
+        block
+    }
+
+    fn silence() -> SampleBlock {
+        [BIAS as u16; BLOCK_LEN]
+    }
+
+    /// White noise over the whole 12-bit range, repeatable
+    fn noise(seed: u32) -> SampleBlock {
+        let mut rng = crate::effects::Rng::new(seed);
+        core::array::from_fn(|_| (rng.next_u32() % 4096) as u16)
+    }
+
+    /// Run `blocks` through a fresh analyzer, returning the last levels
+    fn analyze_all(blocks: impl IntoIterator<Item = SampleBlock>) -> AudioLevels {
+        let mut analyzer = Analyzer::new();
+        let mut levels = AudioLevels::default();
+        for block in blocks {
+            levels = analyzer.analyze(&block);
+        }
+        levels
+    }
+
+    #[test]
+    fn tones_land_in_their_band() {
+        let bass = analyze_all((0..4).map(|i| tone(125.0, 800.0, i * BLOCK_LEN)));
+        assert!(bass.bass > 200 && bass.mid < 40 && bass.treble < 40);
+
+        let mid = analyze_all((0..4).map(|i| tone(1000.0, 800.0, i * BLOCK_LEN)));
+        assert!(mid.mid > 200 && mid.bass < 40 && mid.treble < 40);
+
+        let treble = analyze_all((0..4).map(|i| tone(3000.0, 800.0, i * BLOCK_LEN)));
+        assert!(treble.treble > 200 && treble.bass < 40 && treble.mid < 40);
+    }
+
+    #[test]
+    fn amplitude_comes_out_in_adc_counts() {
+        // Between the N * A / 4 of the center bin and the Hann window's
+        // leak into its neighbours
+        let mut analyzer = Analyzer::new();
+        let levels = analyzer.analyze(&tone(1000.0, 40.0, 0));
+        let amplitude = u32::from(levels.mid) * PEAK_FLOOR / 255;
+        assert!((38..=52).contains(&amplitude), "{}", amplitude);
+    }
+
+    #[test]
+    fn silence_and_bias_alone_are_quiet() {
+        let levels = analyze_all(core::iter::repeat_n(silence(), 8));
+        assert_eq!(
+            (levels.bass, levels.mid, levels.treble, levels.beats),
+            (0, 0, 0, 0)
+        );
+
+        // Off-center bias and hiss below the noise floor
+        let hiss = analyze_all(
+            (0..8).map(|i| tone(2500.0, 2.0, i * BLOCK_LEN).map(|sample| sample - 900)),
+        );
+        assert_eq!((hiss.bass, hiss.mid, hiss.treble), (0, 0, 0));
+    }
+
+    #[test]
+    fn full_scale_noise_does_not_overflow() {
+        let levels = analyze_all((0..32).map(noise));
+        assert!(levels.bass > 0 && levels.mid > 0 && levels.treble > 0);
+
+        // Worst case for the FFT: every sample at a rail
+        let mut analyzer = Analyzer::new();
+        let square: SampleBlock = core::array::from_fn(|i| if i % 2 == 0 { 0 } else { 4095 });
+        analyzer.analyze(&square);
+        analyzer.analyze(&[4095; BLOCK_LEN]);
+        analyzer.analyze(&[0; BLOCK_LEN]);
+    }
+
+    #[test]
+    fn bass_hits_are_beats() {
+        let mut analyzer = Analyzer::new();
+        let mut beats = 0;
+        for i in 0..200 {
+            // A kick every 30 blocks, about 125 BPM
+            let block = if i % 30 < 3 {
+                tone(100.0, 1200.0, i * BLOCK_LEN)
+            } else {
+                silence()
+            };
+            beats = analyzer.analyze(&block).beats;
+        }
+        assert_eq!(beats, 7);
+    }
+
+    #[test]
+    fn beats_are_spaced_out() {
+        // Hits every 5 blocks are closer than `MIN_BEAT_BLOCKS`
+        let mut analyzer = Analyzer::new();
+        let mut beats = 0;
+        for i in 0..140 {
+            let block = if i % 5 == 0 {
+                tone(100.0, 1200.0, i * BLOCK_LEN)
+            } else {
+                silence()
+            };
+            beats = analyzer.analyze(&block).beats;
+        }
+        assert!(beats <= 140 / MIN_BEAT_BLOCKS, "{}", beats);
+        assert!(beats > 0);
+    }
+
+    #[test]
+    fn steady_bass_is_not_a_beat() {
+        let mut analyzer = Analyzer::new();
+        let mut analyze = |i| analyzer.analyze(&tone(100.0, 1200.0, i * BLOCK_LEN)).beats;
+        // Its start may count while the average catches up
+        let settled_beats = (0..40).map(&mut analyze).last().unwrap();
+        assert!(settled_beats <= 2);
+
+        assert_eq!((40..200).map(analyze).last(), Some(settled_beats));
+    }
+}
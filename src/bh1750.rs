@@ -0,0 +1,44 @@
+use embedded_hal::blocking::i2c::{Read, Write};
+
+const ADDRESS: u8 = 0x23; // ADDR pin low
+const POWER_ON: u8 = 0x01;
+const CONTINUOUS_HIGH_RES: u8 = 0x10; // 1 lux resolution, a reading every 120ms
+
+/// BH1750 ambient light sensor, measuring continuously
+///
+/// Readings come back as lux. A missing sensor shows up as an error on
+/// every read, so the caller can keep going with the last level.
+pub struct Bh1750<I2C> {
+    bus: I2C,
+}
+
+impl<I2C, E> Bh1750<I2C>
+where
+    I2C: Read<Error = E> + Write<Error = E>,
+{
+    /// Wrap the bus and start measuring; a failed start is retried on reads
+    pub fn new(bus: I2C) -> Self {
+        let mut sensor = Self { bus };
+        sensor.start().ok();
+        sensor
+    }
+
+    pub fn read_lux(&mut self) -> Result<u16, crate::error::Error> {
+        let mut raw = [0u8; 2];
+        if self.bus.read(ADDRESS, &mut raw).is_err() {
+            // Powered up after us, or replugged
+            self.start().ok();
+            return Err(crate::error::Error::I2C);
+        }
+
+        // Counts are 1.2 per lux in high resolution mode
+        Ok((u32::from(u16::from_be_bytes(raw)) * 10 / 12) as u16)
+    }
+
+    fn start(&mut self) -> Result<(), crate::error::Error> {
+        self.bus
+            .write(ADDRESS, &[POWER_ON])
+            .and_then(|_| self.bus.write(ADDRESS, &[CONTINUOUS_HIGH_RES]))
+            .map_err(|_| crate::error::Error::I2C)
+    }
+}
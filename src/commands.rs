@@ -0,0 +1,463 @@
+use smart_leds::RGB8;
+use smart_leds::hsv::{Hsv, hsv2rgb};
+
+use crate::correction::{Gamma, WHITE_POINT_TYPICAL};
+use crate::effects::{
+    self,
+    custom::{self, USER_COLORS, USER_PALETTES},
+    palette::PaletteEntries,
+    transition::TransitionKind,
+};
+use crate::sequencer::{MIN_SCENE_MS, Playlist, Scene};
+
+/// Starts a command line on the host serial, where an Adalight header would
+/// start with 'A'
+pub const PREFIX: u8 = b'!';
+/// Longest command line, prefix excluded
+pub const MAX_LINE: usize = 128; // Room for a full palette
+
+/// A command line received from the host, without prefix or line end
+#[derive(Clone, Copy)]
+pub struct Line {
+    bytes: [u8; MAX_LINE],
+    len: usize,
+}
+
+impl Line {
+    /// The line as text; `None` if it is not valid UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        core::str::from_utf8(&self.bytes[..self.len]).ok()
+    }
+}
+
+/// Collects the bytes of one command line
+///
+/// Overlong lines are cut off at `MAX_LINE`; the rest is dropped up to the
+/// line end.
+pub struct LineReader {
+    line: Option<Line>, // None outside of a command line
+}
+
+impl LineReader {
+    pub const fn new() -> Self {
+        Self { line: None }
+    }
+
+    /// Whether bytes belong to a command line rather than the Adalight stream
+    pub fn is_active(&self) -> bool {
+        self.line.is_some()
+    }
+
+    /// Start a line after the host sent `PREFIX`
+    pub fn start(&mut self) {
+        self.line = Some(Line {
+            bytes: [0; MAX_LINE],
+            len: 0,
+        });
+    }
+
+    /// Take in one byte; returns the line it completes, if any
+    pub fn feed(&mut self, byte: u8) -> Option<Line> {
+        let line = self.line.as_mut()?;
+
+        match byte {
+            b'\r' | b'\n' => self.line.take(),
+            _ => {
+                if let Some(slot) = line.bytes.get_mut(line.len) {
+                    *slot = byte;
+                    line.len += 1;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Default for LineReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Something the host asked for
+pub enum Command {
+    /// Print the configured scenes
+    SceneList,
+    SceneAdd(Scene),
+    /// Replace the scene at an index
+    SceneSet(usize, Scene),
+    SceneDelete(usize),
+    SceneClear,
+    /// Play a playlist, and play it again on power up once saved
+    Play(Playlist),
+    Stop,
+    /// Print the zones of the fan strip
+    ZoneList,
+    /// Change a setting of a fan strip zone
+    Zone(usize, ZoneSetting),
+    /// Change the color correction of a strip
+    Strip(StripId, StripSetting),
+    /// Set a user color slot
+    Color(usize, RGB8),
+    /// Set a user palette slot from the given colors
+    Palette(usize, PaletteColors),
+    /// Set the animation speed of the fan strip's effect, 64 being normal
+    Speed(u8),
+    /// Switch the fan strip to direct mode and set its colors
+    Direct(DirectFill),
+    /// Fade both strips on or off, as a long button press does
+    Light(bool),
+    /// Store the settings in flash
+    Save,
+}
+
+#[derive(Clone, Copy)]
+pub enum StripId {
+    Fan,
+    Aux,
+}
+
+pub enum StripSetting {
+    Gamma(Gamma),
+    WhitePoint(RGB8),
+}
+
+pub enum DirectFill {
+    Solid(RGB8),
+    /// From the first LED to the last
+    Gradient(RGB8, RGB8),
+}
+
+pub enum ZoneSetting {
+    Mode(u8),
+    /// Relative to the strip brightness
+    Brightness(u8),
+    Reversed(bool),
+}
+
+/// Up to a palette's worth of colors, as given in a command
+pub struct PaletteColors {
+    colors: PaletteEntries,
+    len: usize,
+}
+
+impl PaletteColors {
+    pub fn as_slice(&self) -> &[RGB8] {
+        &self.colors[..self.len]
+    }
+}
+
+/// Parse a command line
+///
+/// ```text
+/// scene list
+/// scene add <mode> <duration ms> [speed] [rrggbb] [transition] [transition ms]
+/// scene set <index> <mode> <duration ms> [...as for add]
+/// scene del <index>
+/// scene clear
+/// seq start | demo | stop
+/// zone list
+/// zone <zone> mode <mode> | brightness <0-255> | reverse on|off
+/// strip fan|aux gamma off|2.2|2.8
+/// strip fan|aux white <rrggbb> | typical
+/// color <slot> rgb <rrggbb> | hsv <hue> <sat> <val> | kelvin <temperature>
+/// palette <slot> <rrggbb> [rrggbb...]
+/// speed <0-255>
+/// direct fill <rrggbb> | gradient <rrggbb> <rrggbb>
+/// light on|off
+/// save
+/// ```
+///
+/// `mode` is an index into the effect registry or an effect name, with
+/// hyphens for its spaces and in any case, e.g. `rainbow-twirl`; transitions are named as in `TransitionKind::name`. Slots count
+/// from 1, as in the names of the "User" effects, and so do zones. Scenes
+/// last at least `MIN_SCENE_MS`.
+pub fn parse(line: &str) -> Result<Command, &'static str> {
+    let mut words = line.split_ascii_whitespace();
+
+    let command = match (words.next(), words.next()) {
+        (Some("scene"), Some("list")) => Command::SceneList,
+        (Some("scene"), Some("add")) => Command::SceneAdd(parse_scene(&mut words)?),
+        (Some("scene"), Some("set")) => {
+            let index = parse_index(words.next())?;
+            Command::SceneSet(index, parse_scene(&mut words)?)
+        }
+        (Some("scene"), Some("del")) => Command::SceneDelete(parse_index(words.next())?),
+        (Some("scene"), Some("clear")) => Command::SceneClear,
+        (Some("seq"), Some("start")) => Command::Play(Playlist::Scenes),
+        (Some("seq"), Some("demo")) => Command::Play(Playlist::Demo),
+        (Some("seq"), Some("stop")) => Command::Stop,
+        (Some("zone"), Some("list")) => Command::ZoneList,
+        (Some("zone"), Some(zone)) => {
+            // Checked against the strip's zones when carried out
+            let zone = parse_slot(zone, usize::MAX)?;
+            let setting = match (words.next(), words.next()) {
+                (Some("mode"), Some(mode)) => ZoneSetting::Mode(parse_mode(mode)?),
+                (Some("brightness"), Some(value)) => {
+                    ZoneSetting::Brightness(value.parse().map_err(|_| "bad brightness")?)
+                }
+                (Some("reverse"), Some("on")) => ZoneSetting::Reversed(true),
+                (Some("reverse"), Some("off")) => ZoneSetting::Reversed(false),
+                _ => return Err("expected mode, brightness or reverse"),
+            };
+            Command::Zone(zone, setting)
+        }
+        (Some("strip"), Some(strip)) => {
+            let strip = match strip {
+                "fan" => StripId::Fan,
+                "aux" => StripId::Aux,
+                _ => return Err("expected fan or aux"),
+            };
+            let setting = match (words.next(), words.next()) {
+                (Some("gamma"), Some(gamma)) => {
+                    StripSetting::Gamma(Gamma::from_name(gamma).ok_or("bad gamma")?)
+                }
+                (Some("white"), Some("typical")) => StripSetting::WhitePoint(WHITE_POINT_TYPICAL),
+                (Some("white"), Some(color)) => {
+                    StripSetting::WhitePoint(parse_color(color).ok_or("bad color")?)
+                }
+                _ => return Err("expected gamma off|2.2|2.8 or white"),
+            };
+            Command::Strip(strip, setting)
+        }
+        (Some("color"), Some(slot)) => {
+            let slot = parse_slot(slot, USER_COLORS)?;
+            Command::Color(slot, parse_color_spec(&mut words)?)
+        }
+        (Some("palette"), Some(slot)) => {
+            let slot = parse_slot(slot, USER_PALETTES)?;
+            let mut palette = PaletteColors {
+                colors: [RGB8::default(); 16],
+                len: 0,
+            };
+            for word in words.by_ref() {
+                let slot = palette
+                    .colors
+                    .get_mut(palette.len)
+                    .ok_or("too many colors")?;
+                *slot = parse_color(word).ok_or("bad color")?;
+                palette.len += 1;
+            }
+            if palette.len == 0 {
+                return Err("missing colors");
+            }
+            Command::Palette(slot, palette)
+        }
+        (Some("speed"), Some(speed)) => Command::Speed(speed.parse().map_err(|_| "bad speed")?),
+        (Some("direct"), Some("fill")) => {
+            Command::Direct(DirectFill::Solid(parse_hex_color(words.next())?))
+        }
+        (Some("direct"), Some("gradient")) => {
+            let from = parse_hex_color(words.next())?;
+            Command::Direct(DirectFill::Gradient(from, parse_hex_color(words.next())?))
+        }
+        (Some("light"), Some("on")) => Command::Light(true),
+        (Some("light"), Some("off")) => Command::Light(false),
+        (Some("save"), None) => Command::Save,
+        _ => return Err("unknown command"),
+    };
+
+    match words.next() {
+        Some(_) => Err("too many arguments"),
+        None => Ok(command),
+    }
+}
+
+fn parse_scene<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<Scene, &'static str> {
+    let mode = parse_mode(words.next().ok_or("missing mode")?)?;
+    let duration_ms = words
+        .next()
+        .ok_or("missing duration")?
+        .parse()
+        .map_err(|_| "bad duration")?;
+    if duration_ms < MIN_SCENE_MS {
+        return Err("duration too short");
+    }
+
+    let mut scene = Scene::new(mode, duration_ms);
+    if let Some(speed) = words.next() {
+        scene.speed = speed.parse().map_err(|_| "bad speed")?;
+    }
+    if let Some(color) = words.next() {
+        scene.color = parse_color(color).ok_or("bad color")?;
+    }
+    if let Some(kind) = words.next() {
+        scene.transition.kind = TransitionKind::from_name(kind).ok_or("bad transition")?;
+    }
+    if let Some(transition_ms) = words.next() {
+        scene.transition.duration_ms = transition_ms
+            .parse::<u16>() // What the stored config keeps
+            .map_err(|_| "bad transition time")?
+            .into();
+    }
+
+    Ok(scene)
+}
+
+/// Registry index or name of an effect
+fn parse_mode(word: &str) -> Result<u8, &'static str> {
+    // Words are split on spaces, so names are given with hyphens instead
+    let is_name = |name: &str| {
+        name.len() == word.len()
+            && name
+                .bytes()
+                .zip(word.bytes())
+                .all(|(name_byte, word_byte)| {
+                    name_byte.eq_ignore_ascii_case(&word_byte)
+                        || (name_byte == b' ' && word_byte == b'-')
+                })
+    };
+
+    word.parse::<u8>()
+        .ok()
+        .filter(|&mode| usize::from(mode) < effects::EFFECTS.len())
+        .or_else(|| {
+            effects::EFFECTS
+                .iter()
+                .position(|effect| is_name(effect.name()))
+                .and_then(|index| u8::try_from(index).ok())
+        })
+        .ok_or("unknown mode")
+}
+
+fn parse_index(word: Option<&str>) -> Result<usize, &'static str> {
+    word.ok_or("missing index")?
+        .parse()
+        .map_err(|_| "bad index")
+}
+
+/// 1-based slot number, checked against `slot_qty` and made 0-based
+fn parse_slot(word: &str, slot_qty: usize) -> Result<usize, &'static str> {
+    match word.parse::<usize>() {
+        Ok(slot) if (1..=slot_qty).contains(&slot) => Ok(slot - 1),
+        _ => Err("bad slot"),
+    }
+}
+
+fn parse_color_spec<'a>(words: &mut impl Iterator<Item = &'a str>) -> Result<RGB8, &'static str> {
+    let format = words.next();
+    let mut number = |what| {
+        words
+            .next()
+            .ok_or("missing value")?
+            .parse::<u16>()
+            .map_err(|_| what)
+    };
+    let mut byte = |what| u8::try_from(number(what)?).map_err(|_| what);
+
+    match format {
+        Some("rgb") => parse_color(words.next().ok_or("missing color")?).ok_or("bad color"),
+        Some("hsv") => Ok(hsv2rgb(Hsv {
+            hue: byte("bad hue")?,
+            sat: byte("bad saturation")?,
+            val: byte("bad value")?,
+        })),
+        Some("kelvin") => Ok(custom::kelvin_to_rgb(number("bad temperature")?)),
+        _ => Err("expected rgb, hsv or kelvin"),
+    }
+}
+
+/// `rrggbb` in hex
+fn parse_hex_color(word: Option<&str>) -> Result<RGB8, &'static str> {
+    parse_color(word.ok_or("missing color")?).ok_or("bad color")
+}
+
+fn parse_color(text: &str) -> Option<RGB8> {
+    if text.len() != 6 {
+        return None;
+    }
+
+    let value = u32::from_str_radix(text, 16).ok()?;
+    let [_, r, g, b] = value.to_be_bytes();
+    Some(RGB8::new(r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene(line: &str) -> Result<Scene, &'static str> {
+        match parse(line)? {
+            Command::SceneAdd(scene) => Ok(scene),
+            _ => panic!("not a scene"),
+        }
+    }
+
+    #[test]
+    fn scenes_take_names_or_indices() {
+        let by_name = scene("scene add Breathe 5000 100 ff8000 fade 300").unwrap();
+        assert_eq!(by_name.mode, effects::index_of("Breathe").unwrap());
+        assert_eq!((by_name.duration_ms, by_name.speed), (5000, 100));
+        assert_eq!(by_name.color, RGB8::new(255, 128, 0));
+        assert_eq!(by_name.transition.duration_ms, 300);
+
+        assert_eq!(scene("scene add 3 1000").unwrap().mode, 3);
+        assert!(scene("scene add Nothing 1000").is_err());
+    }
+
+    #[test]
+    fn multi_word_effects_take_hyphens() {
+        let twirl = effects::index_of("Rainbow Twirl").unwrap();
+        assert_eq!(scene("scene add Rainbow-Twirl 1000").unwrap().mode, twirl);
+        assert_eq!(scene("scene add rainbow-twirl 1000").unwrap().mode, twirl);
+        assert_eq!(
+            scene("scene add Meteor-Rain 2000 100").unwrap().mode,
+            effects::index_of("Meteor Rain").unwrap()
+        );
+        assert!(matches!(
+            parse("zone 1 mode user-color-2"),
+            Ok(Command::Zone(0, ZoneSetting::Mode(mode)))
+                if mode == effects::index_of("User Color 2").unwrap()
+        ));
+        assert_eq!(scene("scene add Rainbow 1000").err(), Some("unknown mode"));
+        assert_eq!(
+            scene("scene add Rainbow-Twirl-2 1000").err(),
+            Some("unknown mode")
+        );
+    }
+
+    #[test]
+    fn short_scenes_are_rejected() {
+        assert_eq!(scene("scene add Comet 0").err(), Some("duration too short"));
+        assert!(scene(&format!("scene add Comet {}", MIN_SCENE_MS - 1)).is_err());
+        assert!(scene(&format!("scene add Comet {}", MIN_SCENE_MS)).is_ok());
+    }
+
+    #[test]
+    fn speed_takes_a_byte() {
+        assert!(matches!(parse("speed 128"), Ok(Command::Speed(128))));
+        assert!(matches!(parse("speed 0"), Ok(Command::Speed(0))));
+        assert_eq!(parse("speed 256").err(), Some("bad speed"));
+        assert_eq!(parse("speed fast").err(), Some("bad speed"));
+        assert!(parse("speed").is_err());
+    }
+
+    #[test]
+    fn direct_fills_take_colors() {
+        assert!(matches!(
+            parse("direct fill ff8000"),
+            Ok(Command::Direct(DirectFill::Solid(color))) if color == RGB8::new(255, 128, 0)
+        ));
+        assert!(matches!(
+            parse("direct gradient ff0000 0000ff"),
+            Ok(Command::Direct(DirectFill::Gradient(from, to)))
+                if from == RGB8::new(255, 0, 0) && to == RGB8::new(0, 0, 255)
+        ));
+        assert_eq!(parse("direct fill").err(), Some("missing color"));
+        assert_eq!(parse("direct fill red").err(), Some("bad color"));
+        assert_eq!(parse("direct gradient ff0000").err(), Some("missing color"));
+        assert_eq!(
+            parse("direct gradient ff0000 00ff00 0000ff").err(),
+            Some("too many arguments")
+        );
+    }
+
+    #[test]
+    fn light_switches_on_and_off() {
+        assert!(matches!(parse("light on"), Ok(Command::Light(true))));
+        assert!(matches!(parse(" light  off "), Ok(Command::Light(false))));
+        assert!(parse("light").is_err());
+        assert!(parse("light dim").is_err());
+        assert_eq!(parse("light on now").err(), Some("too many arguments"));
+    }
+}
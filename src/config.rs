@@ -0,0 +1,341 @@
+use smart_leds::RGB8;
+
+use crate::correction::{ColorCorrection, Gamma};
+use crate::effects;
+use crate::effects::custom::{USER_COLORS, USER_PALETTES, UserColors};
+use crate::effects::palette::PaletteEntries;
+use crate::effects::transition::{Transition, TransitionKind};
+use crate::sequencer::{MAX_SCENES, Playlist, Scene, SceneList};
+
+const MAGIC: &[u8; 4] = b"FCFG";
+const VERSION: u8 = 1;
+/// Effect names are stored zero padded to this length
+const NAME_BYTES: usize = 20;
+/// Effect name, speed, color, transition kind and time, then duration
+const SCENE_BYTES: usize = NAME_BYTES + 11;
+const USER_COLOR_BYTES: usize = USER_PALETTES * size_of::<PaletteEntries>() + USER_COLORS * 3;
+const CORRECTION_BYTES: usize = 2 * 4; // Gamma and white point of both strips
+const HEADER_BYTES: usize = MAGIC.len() + 2; // Magic, version, autoplay
+
+/// Size of a stored config, checksum included
+pub const CONFIG_BYTES: usize =
+    HEADER_BYTES + 1 + MAX_SCENES * SCENE_BYTES + USER_COLOR_BYTES + CORRECTION_BYTES + 2;
+
+/// Settings kept across power cycles
+#[derive(Clone, Copy, Default)]
+pub struct Config {
+    pub scenes: SceneList,
+    /// Playlist started on power up
+    pub autoplay: Option<Playlist>,
+    pub user: UserColors,
+    /// Color correction of each strip; `None` for the one it is configured
+    /// with
+    pub fan_correction: Option<ColorCorrection>,
+    pub aux_correction: Option<ColorCorrection>,
+}
+
+impl Config {
+    /// Serialize into the stored layout: header, scene count, scenes padded
+    /// to `MAX_SCENES`, user colors, strip corrections, then a Fletcher-16
+    /// checksum of all that
+    ///
+    /// Scenes keep the name of their effect, so the registry can change
+    /// between builds.
+    pub fn to_bytes(self) -> [u8; CONFIG_BYTES] {
+        let mut out = [0u8; CONFIG_BYTES];
+        let mut writer = Writer {
+            out: &mut out,
+            pos: 0,
+        };
+
+        writer.bytes(MAGIC);
+        writer.u8(VERSION);
+        writer.u8(match self.autoplay {
+            None => 0,
+            Some(Playlist::Scenes) => 1,
+            Some(Playlist::Demo) => 2,
+        });
+        writer.u8(self.scenes.len() as u8);
+        for scene in self.scenes.as_slice() {
+            let name = effects::EFFECTS
+                .get(usize::from(scene.mode))
+                .map_or("", |effect| effect.name());
+            writer.name(name);
+            writer.u8(scene.speed);
+            writer.bytes(&[scene.color.r, scene.color.g, scene.color.b]);
+            writer.u8(scene.transition.kind as u8);
+            writer.bytes(
+                &(scene.transition.duration_ms.min(u32::from(u16::MAX)) as u16).to_le_bytes(),
+            );
+            writer.bytes(&scene.duration_ms.to_le_bytes());
+        }
+
+        writer.pos = HEADER_BYTES + 1 + MAX_SCENES * SCENE_BYTES;
+        let user_colors = self.user.palettes.iter().flatten().chain(&self.user.colors);
+        for color in user_colors {
+            writer.bytes(&[color.r, color.g, color.b]);
+        }
+        for correction in [self.fan_correction, self.aux_correction] {
+            writer.correction(correction);
+        }
+
+        let checksum = fletcher16(&out[..CONFIG_BYTES - 2]);
+        out[CONFIG_BYTES - 2..].copy_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    /// Parse a stored config; `None` if it is missing or damaged
+    ///
+    /// Scenes of effects that are no longer in the registry are dropped.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..CONFIG_BYTES)?;
+        let (body, checksum) = bytes.split_at(CONFIG_BYTES - 2);
+        if body[..MAGIC.len()] != MAGIC[..]
+            || body[MAGIC.len()] != VERSION
+            || fletcher16(body).to_le_bytes() != checksum
+        {
+            return None;
+        }
+
+        let mut reader = Reader {
+            bytes: body,
+            pos: MAGIC.len() + 1,
+        };
+        let autoplay = match reader.u8() {
+            0 => None,
+            1 => Some(Playlist::Scenes),
+            2 => Some(Playlist::Demo),
+            _ => return None,
+        };
+        let scene_qty = usize::from(reader.u8());
+        if scene_qty > MAX_SCENES {
+            return None;
+        }
+
+        let mut scenes = SceneList::new();
+        for _ in 0..scene_qty {
+            let name = reader.name();
+            let speed = reader.u8();
+            let color = RGB8::new(reader.u8(), reader.u8(), reader.u8());
+            let kind = TransitionKind::from_index(reader.u8())?;
+            let transition_ms = u16::from_le_bytes([reader.u8(), reader.u8()]);
+            let duration_ms =
+                u32::from_le_bytes([reader.u8(), reader.u8(), reader.u8(), reader.u8()]);
+
+            let Some(mode) = name.and_then(effects::index_of) else {
+                continue;
+            };
+            scenes.push(Scene {
+                mode,
+                speed,
+                color,
+                duration_ms,
+                transition: Transition {
+                    kind,
+                    duration_ms: u32::from(transition_ms),
+                },
+            });
+        }
+
+        reader.pos = HEADER_BYTES + 1 + MAX_SCENES * SCENE_BYTES;
+        let mut user = UserColors::default();
+        let user_colors = user.palettes.iter_mut().flatten().chain(&mut user.colors);
+        for color in user_colors {
+            *color = RGB8::new(reader.u8(), reader.u8(), reader.u8());
+        }
+        let fan_correction = reader.correction()?;
+        let aux_correction = reader.correction()?;
+
+        Some(Self {
+            scenes,
+            autoplay,
+            user,
+            fan_correction,
+            aux_correction,
+        })
+    }
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    fn bytes(&mut self, values: &[u8]) {
+        self.out[self.pos..self.pos + values.len()].copy_from_slice(values);
+        self.pos += values.len();
+    }
+
+    /// Gamma as its index plus one, 0 for none, then the white point
+    fn correction(&mut self, correction: Option<ColorCorrection>) {
+        match correction {
+            Some(correction) => {
+                let white = correction.white_point;
+                self.bytes(&[correction.gamma as u8 + 1, white.r, white.g, white.b]);
+            }
+            None => self.pos += 4,
+        }
+    }
+
+    /// `name` zero padded to `NAME_BYTES`, cut off if longer
+    fn name(&mut self, name: &str) {
+        let len = name.len().min(NAME_BYTES);
+        self.bytes(&name.as_bytes()[..len]);
+        self.pos += NAME_BYTES - len;
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    // Only used within the checked length of a config
+    fn u8(&mut self) -> u8 {
+        let value = self.bytes[self.pos];
+        self.pos += 1;
+        value
+    }
+
+    /// Correction as written by `Writer::correction`; `None` for a bad gamma
+    fn correction(&mut self) -> Option<Option<ColorCorrection>> {
+        let gamma = self.u8();
+        let white_point = RGB8::new(self.u8(), self.u8(), self.u8());
+        let Some(index) = gamma.checked_sub(1) else {
+            return Some(None);
+        };
+
+        Some(Some(ColorCorrection {
+            gamma: Gamma::from_index(index)?,
+            white_point,
+        }))
+    }
+
+    /// Zero padded effect name; `None` if it is not text
+    fn name(&mut self) -> Option<&'a str> {
+        let bytes: &'a [u8] = self.bytes;
+        let bytes = &bytes[self.pos..self.pos + NAME_BYTES];
+        self.pos += NAME_BYTES;
+        let len = bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .unwrap_or(NAME_BYTES);
+        core::str::from_utf8(&bytes[..len]).ok()
+    }
+}
+
+fn fletcher16(bytes: &[u8]) -> u16 {
+    let (mut low, mut high) = (0u16, 0u16);
+    for &byte in bytes {
+        low = (low + u16::from(byte)) % 255;
+        high = (high + low) % 255;
+    }
+
+    (high << 8) | low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::transition::TransitionKind;
+
+    fn scene(name: &str, duration_ms: u32) -> Scene {
+        let mut scene = Scene::new(effects::index_of(name).unwrap(), duration_ms);
+        scene.speed = 90;
+        scene.color = RGB8::new(1, 2, 3);
+        scene.transition = Transition {
+            kind: TransitionKind::Wipe,
+            duration_ms: 700,
+        };
+        scene
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut scenes = SceneList::new();
+        scenes.push(scene("Breathe", 5000));
+        scenes.push(scene("Direct", 60_000));
+        let mut user = UserColors::default();
+        user.colors[1] = RGB8::new(0, 128, 128);
+        let config = Config {
+            scenes,
+            autoplay: Some(Playlist::Demo),
+            user,
+            aux_correction: Some(ColorCorrection {
+                gamma: Gamma::Off,
+                white_point: RGB8::new(255, 200, 100),
+            }),
+            ..Config::default()
+        };
+
+        let loaded = Config::from_bytes(&config.to_bytes()).unwrap();
+        assert!(loaded.autoplay == Some(Playlist::Demo));
+        assert_eq!(loaded.scenes.len(), 2);
+        let first = loaded.scenes.as_slice()[0];
+        assert_eq!(first.mode, effects::index_of("Breathe").unwrap());
+        assert_eq!(
+            (first.speed, first.color, first.duration_ms),
+            (90, RGB8::new(1, 2, 3), 5000)
+        );
+        assert!(matches!(first.transition.kind, TransitionKind::Wipe));
+        assert_eq!(first.transition.duration_ms, 700);
+        assert_eq!(
+            loaded.scenes.as_slice()[1].mode,
+            effects::index_of("Direct").unwrap()
+        );
+        assert_eq!(loaded.user.colors[1], RGB8::new(0, 128, 128));
+        assert!(loaded.fan_correction.is_none());
+        let aux = loaded.aux_correction.unwrap();
+        assert!(matches!(aux.gamma, Gamma::Off));
+        assert_eq!(aux.white_point, RGB8::new(255, 200, 100));
+    }
+
+    #[test]
+    fn damage_is_rejected() {
+        let mut bytes = Config::default().to_bytes();
+        bytes[HEADER_BYTES + 3] ^= 1;
+        assert!(Config::from_bytes(&bytes).is_none());
+        assert!(Config::from_bytes(&[0xFF; CONFIG_BYTES]).is_none());
+        let mut bytes = Config::default().to_bytes();
+        bytes[MAGIC.len()] = VERSION + 1;
+        let checksum = fletcher16(&bytes[..CONFIG_BYTES - 2]);
+        bytes[CONFIG_BYTES - 2..].copy_from_slice(&checksum.to_le_bytes());
+        assert!(Config::from_bytes(&bytes).is_none());
+        assert!(Config::from_bytes(&Config::default().to_bytes()[..CONFIG_BYTES - 1]).is_none());
+    }
+
+    #[test]
+    fn every_effect_name_fits() {
+        for effect in effects::EFFECTS {
+            assert!(effect.name().len() <= NAME_BYTES, "{}", effect.name());
+        }
+    }
+
+    #[test]
+    fn unknown_effects_are_dropped() {
+        let mut scenes = SceneList::new();
+        scenes.push(scene("Comet", 1000));
+        scenes.push(scene("Fire", 2000));
+        let mut bytes = Config {
+            scenes,
+            ..Config::default()
+        }
+        .to_bytes();
+        bytes[HEADER_BYTES + 1] = b'X'; // "Comet" is no more
+        let checksum = fletcher16(&bytes[..CONFIG_BYTES - 2]);
+        bytes[CONFIG_BYTES - 2..].copy_from_slice(&checksum.to_le_bytes());
+
+        let loaded = Config::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.scenes.len(), 1);
+        assert_eq!(
+            loaded.scenes.as_slice()[0].mode,
+            effects::index_of("Fire").unwrap()
+        );
+    }
+}
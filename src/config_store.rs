@@ -0,0 +1,75 @@
+use stm32f4xx_hal::flash::{FlashExt, LockedFlash};
+
+use crate::config::{CONFIG_BYTES, Config};
+use crate::error::Error;
+
+/// Flash sector kept out of `memory.x` for settings
+const SECTOR: u8 = 7;
+const SECTOR_OFFSET: usize = 0x6_0000; // From the start of flash
+const SECTOR_BYTES: usize = 0x2_0000;
+/// Space taken by each save; a slot starting with 0xFF has not been written
+///
+/// Fixed with room to spare, so slots stay where they are as the config
+/// grows.
+const SLOT_BYTES: usize = 1024;
+const SLOT_QTY: usize = SECTOR_BYTES / SLOT_BYTES;
+const ERASED: u8 = 0xFF;
+
+const _: () = assert!(CONFIG_BYTES <= SLOT_BYTES, "config outgrew its flash slot");
+
+/// Settings in flash
+///
+/// Each save goes into the next free slot of the sector, so it is only
+/// erased once every `SLOT_QTY` saves. The last slot that holds a valid
+/// config is the current one. A sector without one, e.g. as left by other
+/// firmware, is erased on the next save.
+///
+/// Erasing stalls every flash read, so code and interrupts alike, for a
+/// second or two; the LEDs freeze for that long.
+pub struct ConfigStore {
+    flash: LockedFlash,
+    next_slot: usize,
+}
+
+impl ConfigStore {
+    pub fn new(flash: LockedFlash) -> Self {
+        let mut store = Self {
+            flash,
+            next_slot: 0,
+        };
+        store.next_slot = (0..SLOT_QTY)
+            .find(|&slot| store.slot(slot)[0] == ERASED)
+            .unwrap_or(SLOT_QTY);
+        if store.next_slot > 0 && store.load().is_none() {
+            store.next_slot = SLOT_QTY;
+        }
+        store
+    }
+
+    /// Latest saved config, if there is one
+    pub fn load(&self) -> Option<Config> {
+        (0..self.next_slot)
+            .rev()
+            .find_map(|slot| Config::from_bytes(self.slot(slot)))
+    }
+
+    pub fn save(&mut self, config: Config) -> Result<(), Error> {
+        let mut unlocked = self.flash.unlocked();
+        if self.next_slot == SLOT_QTY {
+            unlocked.erase(SECTOR)?;
+            self.next_slot = 0;
+        }
+
+        let offset = SECTOR_OFFSET + self.next_slot * SLOT_BYTES;
+        // Taken even if programming fails, as the slot may be partly written
+        self.next_slot += 1;
+        unlocked.program(offset, config.to_bytes().iter())?;
+
+        Ok(())
+    }
+
+    fn slot(&self, slot: usize) -> &[u8] {
+        let start = SECTOR_OFFSET + slot * SLOT_BYTES;
+        &self.flash.read()[start..start + SLOT_BYTES]
+    }
+}
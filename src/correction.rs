@@ -0,0 +1,167 @@
+use smart_leds::RGB8;
+
+use crate::effects::scale8;
+
+/// Gamma 2.2 table, the sRGB-like curve; keeps more of the dark end lit
+pub const GAMMA_22: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2,
+    3, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 11, 11,
+    11, 12, 12, 13, 13, 13, 14, 14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 22, 22, 23,
+    23, 24, 25, 25, 26, 26, 27, 28, 28, 29, 30, 30, 31, 32, 33, 33, 34, 35, 35, 36, 37, 38, 39, 39,
+    40, 41, 42, 43, 43, 44, 45, 46, 47, 48, 49, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61,
+    62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 73, 74, 75, 76, 77, 78, 79, 81, 82, 83, 84, 85, 87, 88,
+    89, 90, 91, 93, 94, 95, 97, 98, 99, 100, 102, 103, 105, 106, 107, 109, 110, 111, 113, 114, 116,
+    117, 119, 120, 121, 123, 124, 126, 127, 129, 130, 132, 133, 135, 137, 138, 140, 141, 143, 145,
+    146, 148, 149, 151, 153, 154, 156, 158, 159, 161, 163, 165, 166, 168, 170, 172, 173, 175, 177,
+    179, 181, 182, 184, 186, 188, 190, 192, 194, 196, 197, 199, 201, 203, 205, 207, 209, 211, 213,
+    215, 217, 219, 221, 223, 225, 227, 229, 231, 234, 236, 238, 240, 242, 244, 246, 248, 251, 253,
+    255,
+];
+
+/// Gamma 2.8 table, the one `smart_leds::gamma` uses
+pub const GAMMA_28: [u8; 256] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
+    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
+    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
+    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
+    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
+    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
+    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
+    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
+    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
+    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
+];
+
+/// White point that takes the blue tint off common WS2812 strips
+pub const WHITE_POINT_TYPICAL: RGB8 = RGB8::new(255, 176, 240);
+
+/// Curve from effect colors to LED duty
+#[derive(Clone, Copy)]
+pub enum Gamma {
+    /// Linear, e.g. for a host that sends corrected colors
+    Off,
+    G22,
+    G28,
+}
+
+impl Gamma {
+    pub const ALL: [Gamma; 3] = [Gamma::Off, Gamma::G22, Gamma::G28];
+
+    /// Short name, as used in commands
+    pub fn name(self) -> &'static str {
+        match self {
+            Gamma::Off => "off",
+            Gamma::G22 => "2.2",
+            Gamma::G28 => "2.8",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|gamma| gamma.name() == name)
+    }
+
+    /// Inverse of `gamma as u8`, for stored settings
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(index)).copied()
+    }
+
+    pub fn apply(self, value: u8) -> u8 {
+        match self {
+            Gamma::Off => value,
+            Gamma::G22 => GAMMA_22[usize::from(value)],
+            Gamma::G28 => GAMMA_28[usize::from(value)],
+        }
+    }
+}
+
+/// Color correction of one strip: gamma, then white balance
+#[derive(Clone, Copy)]
+pub struct ColorCorrection {
+    pub gamma: Gamma,
+    /// Scale of each channel after gamma, 255 being full; white is sent as
+    /// this color
+    pub white_point: RGB8,
+}
+
+impl ColorCorrection {
+    /// Gamma 2.8 and no white balance, as strips were driven before
+    pub const fn new() -> Self {
+        Self {
+            gamma: Gamma::G28,
+            white_point: RGB8::new(255, 255, 255),
+        }
+    }
+
+    pub const fn with_white_point(mut self, white_point: RGB8) -> Self {
+        self.white_point = white_point;
+        self
+    }
+
+    pub fn apply(&self, color: RGB8) -> RGB8 {
+        // Balancing after gamma keeps the scale linear in light output
+        RGB8::new(
+            scale8(self.gamma.apply(color.r), self.white_point.r),
+            scale8(self.gamma.apply(color.g), self.white_point.g),
+            scale8(self.gamma.apply(color.b), self.white_point.b),
+        )
+    }
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_keep_the_ends_and_rise() {
+        for gamma in Gamma::ALL {
+            assert_eq!(gamma.apply(0), 0);
+            assert_eq!(gamma.apply(255), 255);
+            assert!((1..=255).all(|value| gamma.apply(value) >= gamma.apply(value - 1)));
+        }
+        // The lower curve keeps more of the dark end
+        assert!((1..255).all(|value| GAMMA_22[value] >= GAMMA_28[value]));
+        assert_eq!(Gamma::G28.apply(128), 37);
+    }
+
+    #[test]
+    fn white_point_scales_after_gamma() {
+        let correction = ColorCorrection::new().with_white_point(RGB8::new(255, 128, 0));
+
+        assert_eq!(
+            correction.apply(RGB8::new(255, 255, 255)),
+            RGB8::new(255, 128, 0)
+        );
+        // Gamma first: half of 2.8's 37, not 2.8 of half
+        assert_eq!(correction.apply(RGB8::new(0, 128, 0)).g, scale8(37, 128));
+    }
+
+    #[test]
+    fn typical_white_point_only_dims() {
+        let correction = ColorCorrection::new().with_white_point(WHITE_POINT_TYPICAL);
+        let plain = ColorCorrection::new();
+
+        for value in [1, 64, 200, 255] {
+            let color = RGB8::new(value, value, value);
+            let (balanced, unbalanced) = (correction.apply(color), plain.apply(color));
+            assert!(balanced.r <= unbalanced.r && balanced.g <= unbalanced.g);
+            assert!(balanced.b <= unbalanced.b);
+        }
+    }
+
+    #[test]
+    fn names_and_indices_round_trip() {
+        for gamma in Gamma::ALL {
+            assert!(Gamma::from_name(gamma.name()).is_some_and(|found| found as u8 == gamma as u8));
+            assert!(Gamma::from_index(gamma as u8).is_some_and(|found| found as u8 == gamma as u8));
+        }
+        assert!(Gamma::from_name("1.0").is_none());
+        assert!(Gamma::from_index(3).is_none());
+    }
+}
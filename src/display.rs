@@ -0,0 +1,384 @@
+use core::fmt::{self, Write};
+
+use crate::effects::reactive::FanTelemetry;
+use crate::error::Error;
+use crate::lcd::Marquee;
+use crate::text::{Fixed, Rpm, TextBuf};
+
+const DUTY_ROW: u8 = 0;
+const GRAPH_ROW: u8 = 4;
+
+/// Character row display the UI screens are drawn on
+///
+/// Positions are (row, column) in cells of the display's normal font. The
+/// graphics methods default to doing nothing so text-only panels like the
+/// HD44780 only need the text and backlight parts.
+pub trait Display {
+    const ROWS: u8;
+    const COLUMNS: usize;
+
+    fn write_at(&mut self, position: (u8, u8), text: &str) -> Result<(), Error>;
+
+    /// Get a `core::fmt::Write` sink starting at `position`
+    fn writer(&mut self, position: (u8, u8)) -> Writer<'_, Self>
+    where
+        Self: Sized,
+    {
+        Writer {
+            display: self,
+            row: position.0,
+            column: usize::from(position.1),
+            error: None,
+        }
+    }
+
+    /// Turn the backlight (or the whole panel, if it has none) on or off
+    fn set_backlight(&mut self, on: bool) -> Result<(), Error>;
+
+    /// Bring the panel back to a blank, usable state after a fault
+    fn reinit(&mut self) -> Result<(), Error>;
+
+    /// Set the inactivity timeout after which the backlight turns off
+    ///
+    /// A timeout of 0 seconds keeps the backlight on.
+    fn set_backlight_timeout(&mut self, secs: u32);
+
+    /// Get the backlight inactivity timeout in seconds, if enabled
+    fn backlight_timeout(&self) -> Option<u32>;
+
+    /// Dim the backlight, 255 being full
+    ///
+    /// Only the OLED dims smoothly; the HD44780 backlight can only be
+    /// switched, so it goes off below a low level instead.
+    fn set_backlight_level(&mut self, _level: u8) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Write text in a double height font, covering `position.0` and the row below
+    fn write_large(&mut self, position: (u8, u8), text: &str) -> Result<(), Error> {
+        self.write_at(position, text)
+    }
+
+    /// Plot `history` from `row` down to the bottom of the display
+    fn draw_history<const N: usize>(
+        &mut self,
+        _row: u8,
+        _history: &History<N>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Push anything drawn so far out to the panel
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Positioned display text sink for `write!`
+///
+/// Text past the last column is dropped rather than wrapped. The error of a
+/// failed write is kept for `take_error`, as `fmt::Error` cannot carry it.
+pub struct Writer<'d, D: Display> {
+    display: &'d mut D,
+    row: u8,
+    column: usize,
+    error: Option<Error>,
+}
+
+impl<D: Display> Writer<'_, D> {
+    /// Error of the write that failed; `Error::Generic` if formatting did
+    pub fn take_error(&mut self) -> Error {
+        self.error.take().unwrap_or(Error::Generic)
+    }
+}
+
+impl<D: Display> fmt::Write for Writer<'_, D> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let free = D::COLUMNS.saturating_sub(self.column);
+        let mut take = s.len().min(free);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+        if take == 0 {
+            return Ok(());
+        }
+
+        let column = u8::try_from(self.column).map_err(|_| fmt::Error)?;
+        if let Err(error) = self.display.write_at((self.row, column), &s[..take]) {
+            self.error = Some(error);
+            return Err(fmt::Error);
+        }
+        self.column += take;
+
+        Ok(())
+    }
+}
+
+/// Display wrapper that goes offline on errors instead of failing callers
+///
+/// A loose wire must not stop fan control, so drawing is skipped while the
+/// display is offline and `try_recover` is polled to bring it back. The
+/// wrapper starts offline; the first successful `try_recover` initializes it.
+pub struct FaultTolerant<D: Display> {
+    device: D,
+    online: bool,
+    backlight_on: bool,  // Wanted state, applied again after a recovery
+    backlight_level: u8, // Likewise
+}
+
+impl<D: Display> FaultTolerant<D> {
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            online: false,
+            backlight_on: true,
+            backlight_level: 255,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Run `draw` if the display is online, taking it offline if that fails
+    pub fn draw(&mut self, draw: impl FnOnce(&mut D) -> Result<(), Error>) {
+        if self.online && draw(&mut self.device).is_err() {
+            defmt::warn!("Display offline");
+            self.online = false;
+        }
+    }
+
+    /// Turn the backlight on or off, now or once the display is back
+    pub fn set_backlight(&mut self, on: bool) {
+        self.backlight_on = on;
+        self.draw(|d| d.set_backlight(on));
+    }
+
+    /// Dim the backlight, now or once the display is back
+    pub fn set_backlight_level(&mut self, level: u8) {
+        self.backlight_level = level;
+        self.draw(|d| d.set_backlight_level(level));
+    }
+
+    /// Backlight state as last set, whether or not the display is online
+    pub fn is_backlight_on(&self) -> bool {
+        self.backlight_on
+    }
+
+    /// Re-initialize an offline display
+    ///
+    /// Returns `true` when the display just came back; everything on it has
+    /// been cleared and should be redrawn.
+    pub fn try_recover(&mut self) -> bool {
+        if self.online || self.device.reinit().is_err() {
+            return false;
+        }
+        // Changes made while offline never reached the panel
+        if self.device.set_backlight(self.backlight_on).is_err()
+            || self
+                .device
+                .set_backlight_level(self.backlight_level)
+                .is_err()
+        {
+            return false;
+        }
+
+        defmt::info!("Display online");
+        self.online = true;
+        true
+    }
+}
+
+/// Ring buffer holding the latest `N` samples for graphing
+#[derive(Clone, Copy)]
+pub struct History<const N: usize> {
+    samples: [u16; N],
+    next: usize,
+    len: usize,
+}
+
+impl<const N: usize> History<N> {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0u16; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: u16) {
+        self.samples[self.next] = sample;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Samples from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        let start = (self.next + N - self.len) % N;
+        (0..self.len).map(move |i| self.samples[(start + i) % N])
+    }
+
+    pub fn max(&self) -> u16 {
+        self.iter().max().unwrap_or(0)
+    }
+}
+
+/// Fan duty cycle, speed and temperature at the top
+///
+/// Displays with room for it show the duty cycle in large text with the
+/// readings on the row below; others fit all three on the top row, e.g.
+/// ` 50% 1.2k  41.5C`. Missing readings show as dashes.
+pub fn show_fan<D: Display>(display: &mut D, fan: &FanTelemetry) -> Result<(), Error> {
+    let duty = fan.duty_percent.min(100);
+    let mut w = if D::ROWS > GRAPH_ROW {
+        let mut text = TextBuf::<16>::new();
+        write!(text, "Fan: {:>3}%", duty).map_err(|_| Error::Generic)?;
+        display.write_large((DUTY_ROW, 0), text.as_str())?;
+        display.writer((DUTY_ROW + 2, 0))
+    } else {
+        let mut w = display.writer((DUTY_ROW, 0));
+        write!(w, "{:>3}% ", duty).map_err(|_| w.take_error())?;
+        w
+    };
+
+    match fan.rpm {
+        Some(rpm) => write!(w, "{:>4} ", Rpm(u32::from(rpm))),
+        None => write!(w, "{:>4} ", "--"),
+    }
+    .map_err(|_| w.take_error())?;
+    match fan.temperature_deci_c {
+        Some(temperature) => write!(w, "{:>5}C", Fixed::new(i32::from(temperature), 1)),
+        None => write!(w, "{:>6}", "--"),
+    }
+    .map_err(|_| w.take_error())?;
+
+    display.flush()
+}
+
+/// Current marquee window, padded to clear the rest of the row
+pub fn show_marquee<D: Display>(display: &mut D, marquee: &Marquee) -> Result<(), Error> {
+    let mut text = TextBuf::<32>::new();
+    write!(text, "{:<1$}", marquee.window(), D::COLUMNS).map_err(|_| Error::Generic)?;
+
+    display.write_at((mode_row::<D>(), 0), text.as_str())?;
+    display.flush()
+}
+
+/// Graph of `history` under the text rows, on displays that can draw
+pub fn show_history<D: Display, const N: usize>(
+    display: &mut D,
+    history: &History<N>,
+) -> Result<(), Error> {
+    display.draw_history(GRAPH_ROW, history)?;
+    display.flush()
+}
+
+// Large duty text and the readings take three rows where the display has
+// room for them
+fn mode_row<D: Display>() -> u8 {
+    if D::ROWS > GRAPH_ROW {
+        DUTY_ROW + 3
+    } else {
+        DUTY_ROW + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Text-only panel recording what is on screen
+    struct Panel<const ROWS: u8> {
+        cells: [[u8; 21]; 6],
+    }
+
+    impl<const ROWS: u8> Panel<ROWS> {
+        fn new() -> Self {
+            Self {
+                cells: [[b' '; 21]; 6],
+            }
+        }
+
+        fn row(&self, row: usize) -> &str {
+            core::str::from_utf8(&self.cells[row][..Self::COLUMNS]).unwrap()
+        }
+    }
+
+    impl<const ROWS: u8> Display for Panel<ROWS> {
+        const ROWS: u8 = ROWS;
+        const COLUMNS: usize = if ROWS > GRAPH_ROW { 21 } else { 16 };
+
+        fn write_at(&mut self, position: (u8, u8), text: &str) -> Result<(), Error> {
+            let row = &mut self.cells[usize::from(position.0)];
+            let start = usize::from(position.1);
+            row[start..start + text.len()].copy_from_slice(text.as_bytes());
+            Ok(())
+        }
+
+        fn set_backlight(&mut self, _on: bool) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn reinit(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn set_backlight_timeout(&mut self, _secs: u32) {}
+
+        fn backlight_timeout(&self) -> Option<u32> {
+            None
+        }
+    }
+
+    #[test]
+    fn writer_cuts_at_last_column() {
+        let mut lcd = Panel::<2>::new();
+        let mut w = lcd.writer((1, 10));
+        write!(w, "{}-{}", 123, "456789").unwrap();
+        assert_eq!(lcd.row(1), "          123-45");
+    }
+
+    #[test]
+    fn fan_fits_one_lcd_row() {
+        let mut lcd = Panel::<2>::new();
+        let fan = FanTelemetry {
+            duty_percent: 50,
+            rpm: Some(1_234),
+            temperature_deci_c: Some(415),
+        };
+        show_fan(&mut lcd, &fan).unwrap();
+        assert_eq!(lcd.row(0), " 50% 1.2k  41.5C");
+
+        show_fan(&mut lcd, &FanTelemetry::default()).unwrap();
+        assert_eq!(lcd.row(0), "  0%   --     --");
+    }
+
+    #[test]
+    fn fan_readings_go_under_large_duty() {
+        let mut oled = Panel::<6>::new();
+        let fan = FanTelemetry {
+            duty_percent: 7,
+            rpm: Some(950),
+            temperature_deci_c: Some(-5),
+        };
+        show_fan(&mut oled, &fan).unwrap();
+        assert!(oled.row(0).starts_with("Fan:   7%"));
+        assert!(oled.row(2).starts_with(" 950  -0.5C"));
+    }
+}
@@ -0,0 +1,53 @@
+use smart_leds::RGB8;
+
+use crate::strip::MAX_LEDS;
+
+/// What `scale8` loses of each channel, in 1/256 of an output step
+pub type Residual = [u8; 3];
+
+/// `color` scaled by (`scale` + 1) / 256 like `scale8`, with the fraction
+/// that would be cut off carried over in `residual`
+///
+/// Feeding the same color frame after frame, the output averages out to the
+/// exact scaled value, so a channel that `scale8` would round down to 0 still
+/// lights up in the right share of frames.
+pub fn scale_dithered(color: RGB8, scale: u8, residual: &mut Residual) -> RGB8 {
+    let channel = |value: u8, residual: &mut u8| {
+        let wide = u16::from(value) * (u16::from(scale) + 1) + u16::from(*residual);
+        *residual = wide as u8;
+        (wide >> 8) as u8
+    };
+    let [r, g, b] = residual;
+
+    RGB8::new(
+        channel(color.r, r),
+        channel(color.g, g),
+        channel(color.b, b),
+    )
+}
+
+/// Temporal dithering state of one strip
+pub struct TemporalDither {
+    residuals: [Residual; MAX_LEDS],
+}
+
+impl TemporalDither {
+    pub const fn new() -> Self {
+        Self {
+            residuals: [[0u8; 3]; MAX_LEDS],
+        }
+    }
+
+    /// Scale `leds` into `out`, carrying each LED's residual to the next frame
+    pub fn scale(&mut self, leds: &[RGB8], scale: u8, out: &mut [RGB8]) {
+        for ((out_led, &led), residual) in out.iter_mut().zip(leds).zip(self.residuals.iter_mut()) {
+            *out_led = scale_dithered(led, scale, residual);
+        }
+    }
+}
+
+impl Default for TemporalDither {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,292 @@
+use smart_leds::{RGB8, colors};
+
+use custom::UserColors;
+use palette::Palette;
+use reactive::{FanTelemetry, Reactive, ReactiveConfig};
+use sound::AudioLevels;
+
+/// Size of per-effect settings tables; `EFFECTS` must not outgrow it
+pub const MAX_EFFECTS: usize = 48;
+/// Update period the per-frame amounts of effects are given for
+pub const NOMINAL_FRAME_MS: u32 = 50;
+/// Effect speed that runs at real time
+pub const NORMAL_SPEED: u8 = 64;
+
+pub mod breathe;
+pub mod clock;
+pub mod comet;
+pub mod custom;
+pub mod direct;
+pub mod fire;
+pub mod meteor;
+pub mod palette;
+pub mod rainbow;
+pub mod reactive;
+pub mod solid;
+pub mod sound;
+pub mod strobe;
+pub mod theater;
+pub mod transition;
+pub mod twinkle;
+pub mod wipe;
+
+/// Settings an effect can be tuned with
+#[derive(Clone, Copy)]
+pub struct EffectParams {
+    /// Main color of effects that draw in a single color
+    pub color: RGB8,
+    /// Latest fan readings for the reactive effects
+    pub fan: FanTelemetry,
+    pub reactive: ReactiveConfig,
+    /// Latest microphone levels for the sound effect
+    pub audio: AudioLevels,
+    /// Colors and palettes of the "User" effects
+    pub user: UserColors,
+}
+
+impl Default for EffectParams {
+    fn default() -> Self {
+        Self {
+            color: colors::DODGER_BLUE,
+            fan: FanTelemetry::default(),
+            reactive: ReactiveConfig::default(),
+            audio: AudioLevels::default(),
+            user: UserColors::default(),
+        }
+    }
+}
+
+/// Everything an effect gets to draw one frame
+pub struct Frame<'a> {
+    /// Output colors, still holding the previous frame
+    pub leds: &'a mut [RGB8],
+    /// One byte per LED that effects may keep between frames, zeroed when the
+    /// effect is selected
+    pub state: &'a mut [u8],
+    /// Lighting clock time
+    pub time_ms: u64,
+    /// Time since the previous frame of this effect, 0 on its first frame
+    pub elapsed_ms: u32,
+    /// Animation rate of this effect; 64 is normal speed, 128 twice as fast,
+    /// 0 stops it
+    pub speed: u8,
+    /// Time as it passes at the effect's speed, counted from when the effect
+    /// was selected; animations should derive their position from this
+    pub scaled_ms: u64,
+    /// Step of `scaled_ms` since the previous frame
+    pub scaled_elapsed_ms: u32,
+    /// Accumulator the effect may advance by `elapsed_ms` so that speed
+    /// changes do not make the animation jump; zeroed like `state`
+    pub phase: &'a mut u32,
+    pub params: &'a EffectParams,
+    pub rng: &'a mut Rng,
+}
+
+impl Frame<'_> {
+    /// `amount` per `NOMINAL_FRAME_MS` of scaled time, converted to this
+    /// frame, so fades and spark rates do not depend on the update period
+    pub fn per_frame(&self, amount: u32) -> u32 {
+        (u64::from(amount) * u64::from(self.scaled_elapsed_ms) / u64::from(NOMINAL_FRAME_MS))
+            .min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Like `per_frame`, for fade amounts out of 256
+    pub fn per_frame_u8(&self, amount: u8) -> u8 {
+        self.per_frame(u32::from(amount)).min(255) as u8
+    }
+}
+
+/// A lighting effect that can be shown on the fan ring
+///
+/// Effects only compute colors; gamma, brightness and output are done by
+/// the caller, so an effect can be rendered on the host as well.
+pub trait Effect: Sync {
+    /// Name shown on the display
+    fn name(&self) -> &'static str;
+
+    fn render(&self, frame: &mut Frame);
+
+    /// Whether the button cycles through this effect
+    fn in_cycle(&self) -> bool {
+        true
+    }
+
+    /// Whether this shows colors written from outside instead of drawing
+    fn is_direct(&self) -> bool {
+        false
+    }
+}
+
+/// All selectable effects, in button order
+///
+/// Adding an effect only needs its module and an entry here. Saved scenes
+/// refer to effects by name, so renaming one drops it from them.
+pub static EFFECTS: &[&dyn Effect] = &[
+    &rainbow::RainbowTwirl,
+    &rainbow::RainbowFade,
+    &palette::PaletteCycle::new("Rainbow Palette", Palette::Entries(&palette::RAINBOW)),
+    &palette::PaletteCycle::new("Forest Palette", Palette::Entries(&palette::FOREST)),
+    &palette::PaletteCycle::new("Cloud Palette", Palette::Entries(&palette::CLOUD)),
+    &palette::PaletteCycle::new("Heat Palette", Palette::Entries(&palette::HEAT)),
+    &palette::PaletteCycle::new("Sunset Palette", Palette::Gradient(&palette::SUNSET)),
+    &breathe::Breathe,
+    &comet::Comet,
+    &theater::TheaterChase,
+    &twinkle::Twinkle,
+    &fire::Fire,
+    &meteor::MeteorRain,
+    &wipe::ColorWipe,
+    &strobe::Strobe,
+    &Reactive::new("Temp Color", true, false, false),
+    &Reactive::new("Fan Spin", false, true, false),
+    &Reactive::new("Load Glow", false, false, true),
+    &Reactive::new("Fan Reactive", true, true, true),
+    &solid::Solid::new("Red Static", colors::RED),
+    &solid::Solid::new("Green Static", colors::GREEN),
+    &solid::Solid::new("Blue Static", colors::BLUE),
+    &solid::Solid::new("White Static", colors::WHITE),
+    &solid::Solid::new("Yellow Static", colors::YELLOW),
+    &solid::Solid::new("Cyan Static", colors::CYAN),
+    &solid::Solid::new("Magenta Static", colors::MAGENTA),
+    &solid::Solid::user("User Color 1", 0),
+    &solid::Solid::user("User Color 2", 1),
+    &solid::Solid::user("User Color 3", 2),
+    &palette::PaletteCycle::new("User Palette 1", Palette::User(0)),
+    &palette::PaletteCycle::new("User Palette 2", Palette::User(1)),
+    &sound::SoundReactive,
+    &direct::Direct,
+];
+
+/// Registry index of the effect called `name`
+pub fn index_of(name: &str) -> Option<u8> {
+    EFFECTS
+        .iter()
+        .position(|effect| effect.name() == name)
+        .and_then(|index| u8::try_from(index).ok())
+}
+
+/// Xorshift PRNG, seeded so that random effects repeat exactly on the host
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub const fn new(seed: u32) -> Self {
+        // Xorshift never leaves zero
+        let state = if seed == 0 { 0x9E37_79B9 } else { seed };
+        Self { state }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u32() >> 24) as u8
+    }
+
+    /// Uniform value in `0..bound`
+    pub fn below(&mut self, bound: u32) -> u32 {
+        ((u64::from(self.next_u32()) * u64::from(bound)) >> 32) as u32
+    }
+}
+
+/// `value * scale / 256`, with 255 leaving the value unchanged
+pub fn scale8(value: u8, scale: u8) -> u8 {
+    ((u16::from(value) * (u16::from(scale) + 1)) >> 8) as u8
+}
+
+pub fn scale_color(color: RGB8, scale: u8) -> RGB8 {
+    RGB8::new(
+        scale8(color.r, scale),
+        scale8(color.g, scale),
+        scale8(color.b, scale),
+    )
+}
+
+/// Mix of `from` and `to`, going fully to `to` as `amount` reaches 255
+pub fn blend(from: RGB8, to: RGB8, amount: u8) -> RGB8 {
+    let mix = |a: u8, b: u8| {
+        let a = i32::from(a);
+        (a + (i32::from(b) - a) * i32::from(amount) / 255) as u8
+    };
+
+    RGB8::new(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b))
+}
+
+/// Dim every LED by `amount`/256 of its current value
+pub fn fade_to_black(leds: &mut [RGB8], amount: u8) {
+    for led in leds.iter_mut() {
+        *led = scale_color(*led, 255 - amount);
+    }
+}
+
+/// Renders effects frame by frame on the host, as `PwmFanRgb` does
+#[cfg(test)]
+pub mod testing {
+    use smart_leds::{RGB8, colors};
+
+    use super::{Effect, EffectParams, Frame, NORMAL_SPEED, Rng};
+
+    pub const COLOR: RGB8 = colors::RED;
+
+    pub struct Runner<const N: usize> {
+        pub leds: [RGB8; N],
+        pub state: [u8; N],
+        pub phase: u32,
+        pub time_ms: u64,
+        pub params: EffectParams,
+        pub rng: Rng,
+    }
+
+    impl<const N: usize> Runner<N> {
+        pub fn new(seed: u32) -> Self {
+            Self {
+                leds: [RGB8::default(); N],
+                state: [0; N],
+                phase: 0,
+                time_ms: 0,
+                params: EffectParams {
+                    color: COLOR,
+                    ..EffectParams::default()
+                },
+                rng: Rng::new(seed),
+            }
+        }
+
+        /// Render the frame `elapsed_ms` after the previous one, at normal speed
+        pub fn render(&mut self, effect: &dyn Effect, elapsed_ms: u32) -> [RGB8; N] {
+            self.time_ms += u64::from(elapsed_ms);
+            effect.render(&mut Frame {
+                leds: &mut self.leds,
+                state: &mut self.state,
+                time_ms: self.time_ms,
+                elapsed_ms,
+                speed: NORMAL_SPEED,
+                scaled_ms: self.time_ms,
+                scaled_elapsed_ms: elapsed_ms,
+                phase: &mut self.phase,
+                params: &self.params,
+                rng: &mut self.rng,
+            });
+            self.leds
+        }
+
+        /// Frames every `step_ms` up to `duration_ms`
+        pub fn run(&mut self, effect: &dyn Effect, step_ms: u32, duration_ms: u32) -> [RGB8; N] {
+            for _ in 0..duration_ms / step_ms {
+                self.render(effect, step_ms);
+            }
+            self.leds
+        }
+    }
+
+    pub fn lit(leds: &[RGB8]) -> usize {
+        leds.iter().filter(|led| **led != RGB8::default()).count()
+    }
+}
@@ -0,0 +1,48 @@
+use super::{Effect, Frame, scale_color, scale8};
+
+const PERIOD_MS: u64 = 4096;
+
+/// Whole ring slowly fading in and out
+pub struct Breathe;
+
+impl Effect for Breathe {
+    fn name(&self) -> &'static str {
+        "Breathe"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        // Triangle wave 0..=255, squared so the dim end lasts longer like
+        // the eye expects
+        let phase = (frame.scaled_ms % PERIOD_MS * 512 / PERIOD_MS) as u16;
+        let ramp = (if phase < 256 { phase } else { 511 - phase }) as u8;
+        let level = scale8(ramp, ramp).max(8);
+
+        frame.leds.fill(scale_color(frame.params.color, level));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner};
+
+    #[test]
+    fn breathes_between_dim_and_full() {
+        let mut runner = Runner::<4>::new(1);
+        let dim = scale_color(COLOR, 8);
+
+        assert_eq!(runner.render(&Breathe, 0), [dim; 4]);
+        assert_eq!(runner.render(&Breathe, 2048), [COLOR; 4]);
+        let falling = runner.render(&Breathe, 1024)[0];
+        assert!(dim.r < falling.r && falling.r < COLOR.r);
+        assert_eq!(runner.render(&Breathe, 1024), [dim; 4]);
+    }
+
+    #[test]
+    fn same_at_any_frame_rate() {
+        let mut fast = Runner::<4>::new(1);
+        let mut slow = Runner::<4>::new(1);
+
+        assert_eq!(fast.run(&Breathe, 10, 3000), slow.run(&Breathe, 50, 3000));
+    }
+}
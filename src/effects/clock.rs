@@ -0,0 +1,151 @@
+use super::NORMAL_SPEED;
+
+/// Time base for the lighting, in milliseconds since start
+///
+/// Built from the 32-bit microsecond monotonic timer, which wraps about
+/// every 71 minutes, by adding up wrapping differences. It keeps counting
+/// through any number of wraps as long as it is read at least once per wrap.
+pub struct LightingClock {
+    last_ticks_us: Option<u32>,
+    elapsed_us: u64,
+}
+
+impl LightingClock {
+    pub const fn new() -> Self {
+        Self {
+            last_ticks_us: None,
+            elapsed_us: 0,
+        }
+    }
+
+    /// Advance to the timer reading `ticks_us` and return the time
+    pub fn now_ms(&mut self, ticks_us: u32) -> u64 {
+        if let Some(last) = self.last_ticks_us {
+            self.elapsed_us += u64::from(ticks_us.wrapping_sub(last));
+        }
+        self.last_ticks_us = Some(ticks_us);
+
+        self.elapsed_us / 1000
+    }
+}
+
+impl Default for LightingClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time running at an adjustable speed, 64 being real time
+///
+/// It is accumulated frame by frame, so changing the speed changes how
+/// fast it runs from then on instead of making it jump.
+#[derive(Clone, Copy, Default)]
+pub struct ScaledTime {
+    ms: u64,
+    remainder: u32, // In 1/NORMAL_SPEED ms
+}
+
+impl ScaledTime {
+    /// Add `elapsed_ms` of real time at `speed` and return the scaled step
+    pub fn advance(&mut self, elapsed_ms: u32, speed: u8) -> u32 {
+        let total = u64::from(self.remainder) + u64::from(elapsed_ms) * u64::from(speed);
+        self.remainder = (total % u64::from(NORMAL_SPEED)) as u32;
+        let step = total / u64::from(NORMAL_SPEED);
+        self.ms += step;
+
+        u32::try_from(step).unwrap_or(u32::MAX)
+    }
+
+    pub fn ms(&self) -> u64 {
+        self.ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::{breathe::Breathe, testing::Runner};
+
+    #[test]
+    fn clock_starts_at_zero() {
+        let mut clock = LightingClock::new();
+
+        assert_eq!(clock.now_ms(123_456_789), 0);
+        assert_eq!(clock.now_ms(123_456_789 + 2_500), 2);
+    }
+
+    #[test]
+    fn clock_counts_through_timer_wrap() {
+        let mut clock = LightingClock::new();
+
+        clock.now_ms(u32::MAX - 499);
+        assert_eq!(clock.now_ms(500), 1); // 1000us across the wrap
+        assert_eq!(clock.now_ms(1_500), 2);
+    }
+
+    #[test]
+    fn clock_keeps_going_past_u32_milliseconds() {
+        let mut clock = LightingClock::new();
+        let mut ticks = 0u32;
+        clock.now_ms(ticks);
+
+        // 3 000 000 steps of 1.5s is 52 days, past the 49.7 day u32 wrap
+        let mut last_ms = 0;
+        for _ in 0..3_000_000 {
+            ticks = ticks.wrapping_add(1_500_000);
+            let now = clock.now_ms(ticks);
+            assert_eq!(now - last_ms, 1_500);
+            last_ms = now;
+        }
+        assert_eq!(last_ms, 4_500_000_000);
+    }
+
+    #[test]
+    fn scaled_time_runs_at_speed() {
+        let mut normal = ScaledTime::default();
+        let mut double = ScaledTime::default();
+        let mut stopped = ScaledTime::default();
+
+        for _ in 0..100 {
+            normal.advance(10, NORMAL_SPEED);
+            double.advance(10, NORMAL_SPEED * 2);
+            stopped.advance(10, 0);
+        }
+        assert_eq!(normal.ms(), 1000);
+        assert_eq!(double.ms(), 2000);
+        assert_eq!(stopped.ms(), 0);
+    }
+
+    #[test]
+    fn slow_speed_keeps_the_remainder() {
+        let mut time = ScaledTime::default();
+
+        // Each 10ms frame at speed 1 is 10/64ms, which would round to nothing
+        for _ in 0..640 {
+            time.advance(10, 1);
+        }
+        assert_eq!(time.ms(), 100);
+    }
+
+    #[test]
+    fn speed_change_does_not_jump() {
+        let mut time = ScaledTime::default();
+        time.advance(1000, NORMAL_SPEED);
+
+        assert_eq!(time.advance(10, NORMAL_SPEED * 3), 30);
+        assert_eq!(time.ms(), 1030);
+    }
+
+    #[test]
+    fn effects_run_smoothly_past_the_u32_wrap() {
+        let mut runner = Runner::<1>::new(1);
+        runner.time_ms = u64::from(u32::MAX) - 20;
+
+        let mut last = runner.render(&Breathe, 10)[0].r;
+        for _ in 0..4 {
+            let level = runner.render(&Breathe, 10)[0].r;
+            assert!(last.abs_diff(level) <= 4, "{last} -> {level}");
+            last = level;
+        }
+    }
+}
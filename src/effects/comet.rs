@@ -0,0 +1,49 @@
+use super::{Effect, Frame, fade_to_black};
+
+const STEP_MS: u64 = 60;
+const TAIL_FADE: u8 = 80; // Fraction of 256 lost per nominal frame
+
+/// Single bright head running around the ring with a fading tail
+pub struct Comet;
+
+impl Effect for Comet {
+    fn name(&self) -> &'static str {
+        "Comet"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.leds.len();
+        if led_qty == 0 {
+            return;
+        }
+
+        fade_to_black(frame.leds, frame.per_frame_u8(TAIL_FADE));
+        let head = (frame.scaled_ms / STEP_MS) as usize % led_qty;
+        frame.leds[head] = frame.params.color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner};
+
+    #[test]
+    fn head_moves_with_fading_tail() {
+        let mut runner = Runner::<8>::new(1);
+
+        let leds = runner.run(&Comet, 20, 300); // Head at 300 / 60 = 5
+        assert_eq!(leds[5], COLOR);
+        assert!(leds[4].r > 0 && leds[4].r < COLOR.r);
+        assert!(leds[3].r < leds[4].r);
+        assert_eq!(leds[6].r, 0);
+    }
+
+    #[test]
+    fn wraps_around_the_ring() {
+        let mut runner = Runner::<8>::new(1);
+
+        let leds = runner.run(&Comet, 20, 8 * 60 + 60);
+        assert_eq!(leds[1], COLOR);
+    }
+}
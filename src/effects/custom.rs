@@ -0,0 +1,102 @@
+use smart_leds::{RGB, RGB8, colors};
+
+use super::blend;
+use super::palette::{self, PaletteEntries};
+
+/// Palettes the user can set, shown by the "User Palette" effects
+pub const USER_PALETTES: usize = 2;
+/// Colors the user can set, shown by the "User Color" effects
+pub const USER_COLORS: usize = 3;
+
+const KELVIN_MIN: u16 = 1000;
+const KELVIN_STEP: u16 = 500;
+
+/// Black body colors from `KELVIN_MIN` on, every `KELVIN_STEP`
+const KELVIN_TABLE: [RGB8; 23] = [
+    RGB::new(255, 68, 0),    // 1000K
+    RGB::new(255, 108, 0),   // 1500K
+    RGB::new(255, 137, 14),  // 2000K
+    RGB::new(255, 159, 70),  // 2500K
+    RGB::new(255, 177, 110), // 3000K
+    RGB::new(255, 193, 141), // 3500K
+    RGB::new(255, 206, 166), // 4000K
+    RGB::new(255, 218, 187), // 4500K
+    RGB::new(255, 228, 206), // 5000K
+    RGB::new(255, 237, 222), // 5500K
+    RGB::new(255, 246, 237), // 6000K
+    RGB::new(255, 254, 250), // 6500K
+    RGB::new(243, 242, 255), // 7000K
+    RGB::new(230, 235, 255), // 7500K
+    RGB::new(221, 230, 255), // 8000K
+    RGB::new(215, 226, 255), // 8500K
+    RGB::new(210, 223, 255), // 9000K
+    RGB::new(205, 220, 255), // 9500K
+    RGB::new(202, 218, 255), // 10000K
+    RGB::new(199, 216, 255), // 10500K
+    RGB::new(196, 214, 255), // 11000K
+    RGB::new(193, 213, 255), // 11500K
+    RGB::new(191, 211, 255), // 12000K
+];
+
+/// Colors and palettes set at runtime
+///
+/// They start out as copies of built-in ones, which stay available as
+/// their own effects.
+#[derive(Clone, Copy)]
+pub struct UserColors {
+    pub palettes: [PaletteEntries; USER_PALETTES],
+    pub colors: [RGB8; USER_COLORS],
+}
+
+impl Default for UserColors {
+    fn default() -> Self {
+        Self {
+            palettes: [palette::RAINBOW, palette::HEAT],
+            colors: [colors::ORANGE, colors::PURPLE, kelvin_to_rgb(2700)],
+        }
+    }
+}
+
+impl UserColors {
+    /// Set the palette in `slot` from `colors`, spread evenly over its
+    /// entries; `false` if there is no such slot or no colors
+    pub fn set_palette(&mut self, slot: usize, colors: &[RGB8]) -> bool {
+        let Some(entries) = self.palettes.get_mut(slot) else {
+            return false;
+        };
+        if colors.is_empty() {
+            return false;
+        }
+
+        // Blending around the loop keeps short palettes seamless
+        let entry_qty = entries.len();
+        for (i, entry) in entries.iter_mut().enumerate() {
+            let index = (i * 65536 / entry_qty) as u16;
+            *entry = palette::color_from_palette(colors, index);
+        }
+        true
+    }
+
+    /// Set the color in `slot`; `false` if there is no such slot
+    pub fn set_color(&mut self, slot: usize, color: RGB8) -> bool {
+        match self.colors.get_mut(slot) {
+            Some(slot) => {
+                *slot = color;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Approximate color of white light at `kelvin`, clamped to 1000..=12000K
+pub fn kelvin_to_rgb(kelvin: u16) -> RGB8 {
+    let offset = kelvin.saturating_sub(KELVIN_MIN);
+    let entry = usize::from(offset / KELVIN_STEP);
+    let Some(&upper) = KELVIN_TABLE.get(entry + 1) else {
+        return KELVIN_TABLE[KELVIN_TABLE.len() - 1];
+    };
+
+    let fraction = (u32::from(offset % KELVIN_STEP) * 255 / u32::from(KELVIN_STEP)) as u8;
+    blend(KELVIN_TABLE[entry], upper, fraction)
+}
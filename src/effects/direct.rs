@@ -0,0 +1,34 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame, blend};
+
+/// Colors set from outside, e.g. by a host over serial
+///
+/// Rendering leaves the LEDs as they are, so whatever was written into the
+/// layer's buffer is shown, gamma corrected and scaled like any effect.
+pub struct Direct;
+
+impl Effect for Direct {
+    fn name(&self) -> &'static str {
+        "Direct"
+    }
+
+    fn render(&self, _frame: &mut Frame) {}
+
+    fn in_cycle(&self) -> bool {
+        false
+    }
+
+    fn is_direct(&self) -> bool {
+        true
+    }
+}
+
+/// Linear blend from `from` on the first LED to `to` on the last
+pub fn fill_gradient(leds: &mut [RGB8], from: RGB8, to: RGB8) {
+    let last = leds.len().saturating_sub(1).max(1);
+
+    for (i, led) in leds.iter_mut().enumerate() {
+        *led = blend(from, to, (i * 255 / last) as u8);
+    }
+}
@@ -0,0 +1,90 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame, scale8};
+
+const COOLING: u32 = 55;
+const SPARKING: u32 = 120; // Out of 256 per nominal frame
+
+/// Flickering flames, after FastLED's Fire2012
+///
+/// The per-LED state holds each cell's heat, which rises away from LED 0,
+/// cools down and is topped up by random sparks near the base.
+pub struct Fire;
+
+impl Effect for Fire {
+    fn name(&self) -> &'static str {
+        "Fire"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.state.len();
+        if led_qty == 0 {
+            return;
+        }
+        let max_cooling = frame.per_frame(COOLING * 10 / led_qty as u32 + 2);
+        let sparking = frame.per_frame(SPARKING);
+
+        let heat = &mut *frame.state;
+        for cell in heat.iter_mut() {
+            *cell = cell.saturating_sub(frame.rng.below(max_cooling) as u8);
+        }
+
+        for k in (2..led_qty).rev() {
+            heat[k] = ((u16::from(heat[k - 1]) + 2 * u16::from(heat[k - 2])) / 3) as u8;
+        }
+
+        if u32::from(frame.rng.next_u8()) < sparking {
+            let y = frame.rng.below(led_qty.min(3) as u32) as usize;
+            heat[y] = heat[y].saturating_add(160 + frame.rng.below(96) as u8);
+        }
+
+        for (led, &cell) in frame.leds.iter_mut().zip(heat.iter()) {
+            *led = heat_color(cell);
+        }
+    }
+}
+
+/// Black through red and yellow to white
+fn heat_color(temperature: u8) -> RGB8 {
+    let t192 = scale8(temperature, 191);
+    let ramp = (t192 & 0x3F) << 2;
+
+    if t192 & 0x80 != 0 {
+        RGB8::new(255, 255, ramp)
+    } else if t192 & 0x40 != 0 {
+        RGB8::new(255, ramp, 0)
+    } else {
+        RGB8::new(ramp, 0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{Runner, lit};
+
+    #[test]
+    fn repeats_with_the_same_seed() {
+        let mut first = Runner::<16>::new(3);
+        let mut second = Runner::<16>::new(3);
+
+        assert_eq!(first.run(&Fire, 20, 3000), second.run(&Fire, 20, 3000));
+    }
+
+    #[test]
+    fn flames_burn_from_the_base() {
+        let mut runner = Runner::<16>::new(3);
+
+        let leds = runner.run(&Fire, 20, 3000);
+        assert!(lit(&leds[..4]) > 0);
+        // Warm colors only: red leads, blue only near white hot
+        assert!(leds.iter().all(|led| led.r >= led.g && led.g >= led.b));
+    }
+
+    #[test]
+    fn heat_color_ramps_to_white() {
+        assert_eq!(heat_color(0), RGB8::default());
+        assert_eq!(heat_color(255), RGB8::new(255, 255, 252));
+        assert!(heat_color(80).g < heat_color(160).g);
+    }
+}
@@ -0,0 +1,74 @@
+use super::{Effect, Frame, scale_color};
+
+const STEP_MS: u64 = 50;
+const METEOR_SIZE: usize = 2;
+const TRAIL_DECAY: u8 = 64; // Fraction of 256 lost when a trail LED decays
+
+/// Meteor crossing the ring, leaving an unevenly decaying trail
+pub struct MeteorRain;
+
+impl Effect for MeteorRain {
+    fn name(&self) -> &'static str {
+        "Meteor Rain"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.leds.len();
+        if led_qty == 0 {
+            return;
+        }
+
+        // Random per-LED decay breaks the trail up
+        let decay = frame.per_frame_u8(TRAIL_DECAY);
+        for led in frame.leds.iter_mut() {
+            if frame.rng.next_u8() > 128 {
+                *led = scale_color(*led, 255 - decay);
+            }
+        }
+
+        // The head runs off the end and the trail fades before it comes back
+        let position = (frame.scaled_ms / STEP_MS) as usize % (led_qty * 2);
+        for i in 0..METEOR_SIZE {
+            if let Some(led) = position.checked_sub(i).and_then(|p| frame.leds.get_mut(p)) {
+                *led = frame.params.color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner, lit};
+
+    #[test]
+    fn head_leaves_a_decaying_trail() {
+        let mut runner = Runner::<10>::new(5);
+
+        let leds = runner.run(&MeteorRain, 50, 300); // Head at 300 / 50 = 6
+        assert_eq!(leds[6], COLOR);
+        assert_eq!(leds[5], COLOR);
+        // LEDs the head left more than a frame ago have decayed at least once
+        assert!(leds[..4].iter().all(|led| led.r > 0 && led.r < COLOR.r));
+        assert_eq!(lit(&leds[7..]), 0);
+    }
+
+    #[test]
+    fn trail_fades_while_the_head_is_off_the_end() {
+        let mut runner = Runner::<10>::new(5);
+
+        let leds = runner.run(&MeteorRain, 50, 19 * 50);
+        assert!(leds.iter().all(|led| led.r < COLOR.r / 2));
+    }
+
+    #[test]
+    fn repeats_with_the_same_seed() {
+        let mut first = Runner::<10>::new(9);
+        let mut second = Runner::<10>::new(9);
+
+        assert_eq!(
+            first.run(&MeteorRain, 20, 1500),
+            second.run(&MeteorRain, 20, 1500)
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use smart_leds::{RGB, RGB8, colors};
+
+use super::{Effect, Frame, blend, custom::UserColors};
+
+const LED_SPACING: u16 = 4096; // 1/16 of the palette between neighbouring LEDs
+const SCROLL_MS: u64 = 1600; // Time for the ring to scroll through the whole palette
+
+pub type PaletteEntries = [RGB8; 16];
+
+pub const FOREST: PaletteEntries = [
+    colors::DARK_GREEN,
+    colors::DARK_GREEN,
+    colors::DARK_OLIVE_GREEN,
+    colors::DARK_GREEN,
+    colors::GREEN,
+    colors::FOREST_GREEN,
+    colors::OLIVE_DRAB,
+    colors::GREEN,
+    colors::SEA_GREEN,
+    colors::MEDIUM_AQUAMARINE,
+    colors::LIME_GREEN,
+    colors::YELLOW_GREEN,
+    colors::LIGHT_GREEN,
+    colors::LAWN_GREEN,
+    colors::MEDIUM_AQUAMARINE,
+    colors::FOREST_GREEN,
+];
+
+pub const CLOUD: PaletteEntries = [
+    colors::BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::DARK_BLUE,
+    colors::BLUE,
+    colors::DARK_BLUE,
+    colors::SKY_BLUE,
+    colors::SKY_BLUE,
+    colors::LIGHT_BLUE,
+    colors::WHITE,
+    colors::LIGHT_BLUE,
+    colors::SKY_BLUE,
+];
+
+pub const HEAT: PaletteEntries = [
+    RGB::new(0, 0, 0),
+    RGB::new(0x33, 0, 0),
+    RGB::new(0x66, 0, 0),
+    RGB::new(0x99, 0, 0),
+    RGB::new(0xCC, 0, 0),
+    RGB::new(0xFF, 0, 0),
+    RGB::new(0xFF, 0x33, 0),
+    RGB::new(0xFF, 0x66, 0),
+    RGB::new(0xFF, 0x99, 0),
+    RGB::new(0xFF, 0xCC, 0),
+    RGB::new(0xFF, 0xFF, 0),
+    RGB::new(0xFF, 0xFF, 0x33),
+    RGB::new(0xFF, 0xFF, 0x66),
+    RGB::new(0xFF, 0xFF, 0x99),
+    RGB::new(0xFF, 0xFF, 0xCC),
+    RGB::new(0xFF, 0xFF, 0xFF),
+];
+
+pub const RAINBOW: PaletteEntries = [
+    RGB::new(0xFF, 0, 0),
+    RGB::new(0xD5, 0x2A, 0),
+    RGB::new(0xAB, 0x55, 0),
+    RGB::new(0xAB, 0x7F, 0),
+    RGB::new(0xAB, 0xAB, 0),
+    RGB::new(0x56, 0xD5, 0),
+    RGB::new(0, 0xFF, 0),
+    RGB::new(0, 0xD5, 0x2A),
+    RGB::new(0, 0xAB, 0x55),
+    RGB::new(0, 0x56, 0xAA),
+    RGB::new(0, 0, 0xFF),
+    RGB::new(0x2A, 0, 0xD5),
+    RGB::new(0x55, 0, 0xAB),
+    RGB::new(0x7F, 0, 0x81),
+    RGB::new(0xAB, 0, 0x55),
+    RGB::new(0xD5, 0, 0x2B),
+];
+
+pub const SUNSET: [GradientStop; 7] = [
+    GradientStop::new(0, RGB::new(120, 0, 0)),
+    GradientStop::new(22, RGB::new(179, 22, 0)),
+    GradientStop::new(51, RGB::new(255, 104, 0)),
+    GradientStop::new(85, RGB::new(167, 22, 18)),
+    GradientStop::new(135, RGB::new(100, 0, 103)),
+    GradientStop::new(198, RGB::new(16, 0, 130)),
+    GradientStop::new(255, RGB::new(0, 0, 160)),
+];
+
+/// Color at `position` (0..=255) of a gradient palette
+#[derive(Clone, Copy)]
+pub struct GradientStop {
+    pub position: u8,
+    pub color: RGB8,
+}
+
+impl GradientStop {
+    pub const fn new(position: u8, color: RGB8) -> Self {
+        Self { position, color }
+    }
+}
+
+/// Colors to pick from, either as evenly spaced entries or gradient stops
+#[derive(Clone, Copy)]
+pub enum Palette {
+    /// Entries spread evenly around a loop, the last blending into the first
+    Entries(&'static [RGB8]),
+    /// Stops in ascending position; colors before the first or after the
+    /// last stop are held
+    Gradient(&'static [GradientStop]),
+    /// One of the `UserColors::palettes`, by slot
+    User(usize),
+}
+
+impl Palette {
+    /// Blended color at `index`, where 65536 is once through the palette
+    pub fn color_at(&self, index: u16, user: &UserColors) -> RGB8 {
+        match self {
+            Palette::Entries(entries) => color_from_palette(entries, index),
+            Palette::Gradient(stops) => color_from_gradient(stops, index),
+            Palette::User(slot) => user
+                .palettes
+                .get(*slot)
+                .map(|entries| color_from_palette(entries, index))
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Linearly blended palette color at a fractional `index`
+///
+/// The palette is treated as a loop: `index` 0 is the first entry and each
+/// following entry is 65536 / `entries.len()` further.
+pub fn color_from_palette(entries: &[RGB8], index: u16) -> RGB8 {
+    if entries.is_empty() {
+        return RGB8::default();
+    }
+
+    let scaled = u32::from(index) * entries.len() as u32;
+    let entry = (scaled >> 16) as usize;
+    let fraction = (scaled >> 8) as u8;
+
+    blend(
+        entries[entry],
+        entries[(entry + 1) % entries.len()],
+        fraction,
+    )
+}
+
+/// Linearly blended gradient color at `index`, where 65535 is the last stop
+pub fn color_from_gradient(stops: &[GradientStop], index: u16) -> RGB8 {
+    let position = |stop: &GradientStop| u32::from(stop.position) * 257;
+    let index = u32::from(index);
+
+    let Some(upper) = stops.iter().position(|stop| position(stop) >= index) else {
+        return stops.last().map(|stop| stop.color).unwrap_or_default();
+    };
+    if upper == 0 {
+        return stops[0].color;
+    }
+
+    let (from, to) = (&stops[upper - 1], &stops[upper]);
+    let span = (position(to) - position(from)).max(1);
+    let fraction = ((index - position(from)) * 255 / span) as u8;
+
+    blend(from.color, to.color, fraction)
+}
+
+/// Palette scrolling smoothly along the ring
+pub struct PaletteCycle {
+    name: &'static str,
+    palette: Palette,
+}
+
+impl PaletteCycle {
+    pub const fn new(name: &'static str, palette: Palette) -> Self {
+        Self { name, palette }
+    }
+}
+
+impl Effect for PaletteCycle {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        // Truncating keeps the scroll position in one loop of the palette
+        let start = (frame.scaled_ms * 65536 / SCROLL_MS) as u16;
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            let index = start.wrapping_add(LED_SPACING.wrapping_mul(i as u16));
+            *led = self.palette.color_at(index, &frame.params.user);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use smart_leds::hsv::{Hsv, hsv2rgb};
+
+use super::{Effect, Frame};
+
+const TWIRL_HUE_STEP_MS: u64 = 20; // Rotation time per hue step
+const FADE_HUE_STEP_MS: u64 = 30;
+
+/// Rainbow spread around the ring and rotating
+pub struct RainbowTwirl;
+
+/// Whole ring fading through the rainbow
+pub struct RainbowFade;
+
+impl Effect for RainbowTwirl {
+    fn name(&self) -> &'static str {
+        "Rainbow Twirl"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.leds.len().max(1);
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            let hue =
+                ((frame.scaled_ms / TWIRL_HUE_STEP_MS + (i * 256 / led_qty) as u64) % 256) as u8;
+            *led = hsv2rgb(Hsv {
+                hue,
+                sat: 255,
+                val: 255,
+            });
+        }
+    }
+}
+
+impl Effect for RainbowFade {
+    fn name(&self) -> &'static str {
+        "Rainbow Fade"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let hue = (frame.scaled_ms / FADE_HUE_STEP_MS % 256) as u8;
+        let color = hsv2rgb(Hsv {
+            hue,
+            sat: 255,
+            val: 255,
+        });
+
+        frame.leds.fill(color);
+    }
+}
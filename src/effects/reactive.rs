@@ -0,0 +1,243 @@
+use smart_leds::hsv::{Hsv, hsv2rgb};
+
+use super::{Effect, Frame};
+
+const HUE_SPREAD: u32 = 24; // Hue range around the ring when the hue is mapped
+const HUE_STEP_MS: u64 = 20; // Rotation time per hue step at speed 64
+
+/// What the fan is doing, as seen by the lighting
+#[derive(Clone, Copy, Default)]
+pub struct FanTelemetry {
+    pub duty_percent: u8,
+    /// Tachometer reading, if a tach input is wired
+    pub rpm: Option<u16>,
+    /// Temperature in tenths of a degree Celsius, if a sensor is wired
+    pub temperature_deci_c: Option<i16>,
+}
+
+/// Fan reading a reactive mapping is driven by
+#[derive(Clone, Copy)]
+pub enum Source {
+    Duty,
+    Rpm,
+    Temperature,
+}
+
+impl FanTelemetry {
+    pub fn value(&self, source: Source) -> Option<i32> {
+        match source {
+            Source::Duty => Some(i32::from(self.duty_percent)),
+            Source::Rpm => self.rpm.map(i32::from),
+            Source::Temperature => self.temperature_deci_c.map(i32::from),
+        }
+    }
+}
+
+/// Straight line from (`in_min`, `out_min`) to (`in_max`, `out_max`),
+/// clamped at both ends
+///
+/// `out_min` may be larger than `out_max` to map a rising input to a
+/// falling output.
+#[derive(Clone, Copy)]
+pub struct LinearMap {
+    pub in_min: i32,
+    pub in_max: i32,
+    pub out_min: i32,
+    pub out_max: i32,
+}
+
+impl LinearMap {
+    pub const fn new(in_min: i32, in_max: i32, out_min: i32, out_max: i32) -> Self {
+        Self {
+            in_min,
+            in_max,
+            out_min,
+            out_max,
+        }
+    }
+
+    pub fn map(&self, input: i32) -> i32 {
+        if self.in_max <= self.in_min || input <= self.in_min {
+            return self.out_min;
+        }
+        if input >= self.in_max {
+            return self.out_max;
+        }
+
+        let span_in = i64::from(self.in_max) - i64::from(self.in_min);
+        let span_out = i64::from(self.out_max) - i64::from(self.out_min);
+        let offset = (i64::from(input) - i64::from(self.in_min)) * span_out / span_in;
+
+        (i64::from(self.out_min) + offset) as i32
+    }
+}
+
+/// A fan reading mapped onto one lighting property
+#[derive(Clone, Copy)]
+pub struct Mapping {
+    pub source: Source,
+    pub map: LinearMap,
+}
+
+impl Mapping {
+    /// Mapped value of the reading, or `out_min` when the reading is missing
+    pub fn apply(&self, fan: &FanTelemetry) -> i32 {
+        match fan.value(self.source) {
+            Some(value) => self.map.map(value),
+            None => self.map.out_min,
+        }
+    }
+}
+
+/// How fan readings drive the reactive effects
+#[derive(Clone, Copy)]
+pub struct ReactiveConfig {
+    /// Hue, 0 (red) to 255
+    pub hue: Mapping,
+    /// Effect speed as in `EffectParams::speed`
+    pub speed: Mapping,
+    /// Brightness, 0 to 255
+    pub brightness: Mapping,
+}
+
+impl Default for ReactiveConfig {
+    fn default() -> Self {
+        Self {
+            // 25C blue to 60C red
+            hue: Mapping {
+                source: Source::Temperature,
+                map: LinearMap::new(250, 600, 160, 0),
+            },
+            speed: Mapping {
+                source: Source::Duty,
+                map: LinearMap::new(0, 100, 16, 255),
+            },
+            brightness: Mapping {
+                source: Source::Duty,
+                map: LinearMap::new(0, 100, 48, 255),
+            },
+        }
+    }
+}
+
+/// Rotating hue ring with hue, speed and brightness following the fan
+///
+/// Properties that are not reactive fall back to a full rainbow, the
+/// configured speed and full brightness.
+pub struct Reactive {
+    name: &'static str,
+    hue: bool,
+    speed: bool,
+    brightness: bool,
+}
+
+impl Reactive {
+    pub const fn new(name: &'static str, hue: bool, speed: bool, brightness: bool) -> Self {
+        Self {
+            name,
+            hue,
+            speed,
+            brightness,
+        }
+    }
+}
+
+impl Effect for Reactive {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let config = &frame.params.reactive;
+        let fan = &frame.params.fan;
+        let led_qty = frame.leds.len().max(1) as u32;
+
+        let speed = if self.speed {
+            config.speed.apply(fan).clamp(0, 255) as u64
+        } else {
+            u64::from(frame.speed)
+        };
+        let val = if self.brightness {
+            config.brightness.apply(fan).clamp(0, 255) as u8
+        } else {
+            255
+        };
+
+        // Accumulated in 1/256 hue steps so speed changes never make it jump
+        let advance = u64::from(frame.elapsed_ms) * speed * 256 / (HUE_STEP_MS * 64);
+        *frame.phase = frame.phase.wrapping_add(advance as u32);
+        let rotation = *frame.phase >> 8;
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            let offset = (rotation + i as u32 * 256 / led_qty) % 256;
+            let hue = if self.hue {
+                // Swing back and forth around the mapped hue instead of
+                // running through the whole rainbow
+                let base_hue = config.hue.apply(fan).clamp(0, 255) as u32;
+                let swing = if offset < 128 { offset } else { 255 - offset };
+                base_hue + 256 - HUE_SPREAD / 2 + swing * HUE_SPREAD / 128
+            } else {
+                offset
+            };
+            *led = hsv2rgb(Hsv {
+                hue: (hue % 256) as u8,
+                sat: 255,
+                val,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_hits_its_endpoints() {
+        let map = LinearMap::new(250, 600, 160, 0);
+        assert_eq!(map.map(250), 160);
+        assert_eq!(map.map(600), 0);
+        assert_eq!(map.map(425), 80);
+    }
+
+    #[test]
+    fn map_clamps_outside_the_input_range() {
+        let map = LinearMap::new(0, 100, 16, 255);
+        assert_eq!(map.map(-40), 16);
+        assert_eq!(map.map(i32::MIN), 16);
+        assert_eq!(map.map(101), 255);
+        assert_eq!(map.map(i32::MAX), 255);
+    }
+
+    #[test]
+    fn empty_input_range_gives_out_min() {
+        assert_eq!(LinearMap::new(50, 50, 10, 20).map(50), 10);
+        assert_eq!(LinearMap::new(60, 50, 10, 20).map(100), 10);
+    }
+
+    #[test]
+    fn mapping_reads_its_source() {
+        let fan = FanTelemetry {
+            duty_percent: 50,
+            rpm: Some(1500),
+            temperature_deci_c: None,
+        };
+        let duty = Mapping {
+            source: Source::Duty,
+            map: LinearMap::new(0, 100, 0, 200),
+        };
+        let rpm = Mapping {
+            source: Source::Rpm,
+            map: LinearMap::new(0, 3000, 0, 255),
+        };
+        let temperature = Mapping {
+            source: Source::Temperature,
+            map: LinearMap::new(250, 600, 160, 0),
+        };
+
+        assert_eq!(duty.apply(&fan), 100);
+        assert_eq!(rpm.apply(&fan), 127);
+        // Missing readings fall back to the low end
+        assert_eq!(temperature.apply(&fan), 160);
+    }
+}
@@ -0,0 +1,52 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame};
+
+#[derive(Clone, Copy)]
+enum SolidColor {
+    Fixed(RGB8),
+    User(usize), // Slot in `UserColors::colors`
+}
+
+/// One static color on every LED
+pub struct Solid {
+    name: &'static str,
+    color: SolidColor,
+}
+
+impl Solid {
+    pub const fn new(name: &'static str, color: RGB8) -> Self {
+        Self {
+            name,
+            color: SolidColor::Fixed(color),
+        }
+    }
+
+    /// Showing the user color in `slot`, which can be changed at runtime
+    pub const fn user(name: &'static str, slot: usize) -> Self {
+        Self {
+            name,
+            color: SolidColor::User(slot),
+        }
+    }
+}
+
+impl Effect for Solid {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let color = match self.color {
+            SolidColor::Fixed(color) => color,
+            SolidColor::User(slot) => frame
+                .params
+                .user
+                .colors
+                .get(slot)
+                .copied()
+                .unwrap_or_default(),
+        };
+        frame.leds.fill(color);
+    }
+}
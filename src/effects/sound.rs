@@ -0,0 +1,64 @@
+use smart_leds::RGB8;
+use smart_leds::hsv::{Hsv, hsv2rgb};
+
+use super::{Effect, Frame, blend};
+
+const BASS_HUE: u32 = 0; // Red
+const MID_HUE: u32 = 85; // Green
+const TREBLE_HUE: u32 = 170; // Blue
+const HUE_SPREAD: u32 = 32; // Hue change around the ring
+const FLASH_DECAY: u8 = 40; // Per nominal frame, out of 255
+const MIN_LEVEL: u8 = 8; // Keeps the ring faintly lit in silence
+
+/// Latest microphone analysis, see `audio::Analyzer`
+#[derive(Clone, Copy, Default)]
+pub struct AudioLevels {
+    /// Band levels relative to the recent loudness, 255 being the loudest
+    pub bass: u8,
+    pub mid: u8,
+    pub treble: u8,
+    /// Beats detected so far, wrapping; a change means a new beat
+    pub beats: u8,
+}
+
+/// Music visualizer: hue from the balance of the bands, brightness from
+/// the loudness, and a white flash on every beat
+pub struct SoundReactive;
+
+impl Effect for SoundReactive {
+    fn name(&self) -> &'static str {
+        "Sound"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let audio = frame.params.audio;
+
+        // Phase keeps the beat count last seen and the flash level
+        let last_beats = *frame.phase as u8;
+        let flash = if audio.beats != last_beats {
+            255
+        } else {
+            ((*frame.phase >> 8) as u8).saturating_sub(frame.per_frame_u8(FLASH_DECAY))
+        };
+        *frame.phase = u32::from(flash) << 8 | u32::from(audio.beats);
+
+        let (bass, mid, treble) = (
+            u32::from(audio.bass),
+            u32::from(audio.mid),
+            u32::from(audio.treble),
+        );
+        let total = (bass + mid + treble).max(1);
+        let hue = (bass * BASS_HUE + mid * MID_HUE + treble * TREBLE_HUE) / total;
+        let val = audio.bass.max(audio.mid).max(audio.treble).max(MIN_LEVEL);
+        let led_qty = frame.leds.len().max(1) as u32;
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            let color = hsv2rgb(Hsv {
+                hue: ((hue + i as u32 * HUE_SPREAD / led_qty) % 256) as u8,
+                sat: 255,
+                val,
+            });
+            *led = blend(color, RGB8::new(255, 255, 255), flash / 2);
+        }
+    }
+}
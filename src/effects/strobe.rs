@@ -0,0 +1,40 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame};
+
+const PERIOD_MS: u64 = 500;
+const FLASH_MS: u64 = 40;
+
+/// Short full-ring flashes
+pub struct Strobe;
+
+impl Effect for Strobe {
+    fn name(&self) -> &'static str {
+        "Strobe"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let color = if frame.scaled_ms % PERIOD_MS < FLASH_MS {
+            frame.params.color
+        } else {
+            RGB8::default()
+        };
+
+        frame.leds.fill(color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner};
+
+    #[test]
+    fn flashes_once_per_period() {
+        let mut runner = Runner::<3>::new(1);
+
+        assert_eq!(runner.render(&Strobe, 0), [COLOR; 3]);
+        assert_eq!(runner.render(&Strobe, 40), [RGB8::default(); 3]);
+        assert_eq!(runner.render(&Strobe, 460), [COLOR; 3]);
+    }
+}
@@ -0,0 +1,47 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame};
+
+const STEP_MS: u64 = 120;
+const SPACING: usize = 3;
+
+/// Every third LED lit, marching like theater marquee lights
+pub struct TheaterChase;
+
+impl Effect for TheaterChase {
+    fn name(&self) -> &'static str {
+        "Theater Chase"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let offset = (frame.scaled_ms / STEP_MS) as usize % SPACING;
+        let color = frame.params.color;
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            *led = if (i + SPACING - offset) % SPACING == 0 {
+                color
+            } else {
+                RGB8::default()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner, lit};
+
+    #[test]
+    fn every_third_led_marches_on() {
+        let mut runner = Runner::<9>::new(1);
+
+        let leds = runner.render(&TheaterChase, 0);
+        assert_eq!(lit(&leds), 3);
+        assert!([0, 3, 6].iter().all(|&i| leds[i] == COLOR));
+
+        let leds = runner.render(&TheaterChase, 120);
+        assert!([1, 4, 7].iter().all(|&i| leds[i] == COLOR));
+        assert_eq!(leds[0], RGB8::default());
+    }
+}
@@ -0,0 +1,105 @@
+use smart_leds::RGB8;
+
+use super::blend;
+
+/// How the ring changes over from one effect to the next
+#[derive(Clone, Copy)]
+pub enum TransitionKind {
+    /// Switch at once
+    Cut,
+    /// Blend all LEDs from the old to the new effect
+    Crossfade,
+    /// New effect sweeping in from the first LED
+    Wipe,
+    /// Old effect fading out, then the new one fading in
+    FadeThroughBlack,
+}
+
+impl TransitionKind {
+    pub const ALL: [TransitionKind; 4] = [
+        TransitionKind::Cut,
+        TransitionKind::Crossfade,
+        TransitionKind::Wipe,
+        TransitionKind::FadeThroughBlack,
+    ];
+
+    /// Short name, as used in commands
+    pub fn name(self) -> &'static str {
+        match self {
+            TransitionKind::Cut => "cut",
+            TransitionKind::Crossfade => "fade",
+            TransitionKind::Wipe => "wipe",
+            TransitionKind::FadeThroughBlack => "black",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Inverse of `kind as u8`, for stored settings
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(index)).copied()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Transition {
+    pub kind: TransitionKind,
+    pub duration_ms: u32,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self {
+            kind: TransitionKind::Crossfade,
+            duration_ms: 600,
+        }
+    }
+}
+
+impl Transition {
+    /// Progress at `elapsed_ms` into the transition, `None` once it is over
+    pub fn progress(&self, elapsed_ms: u32) -> Option<u8> {
+        if elapsed_ms >= self.duration_ms {
+            return None;
+        }
+
+        Some((u64::from(elapsed_ms) * 256 / u64::from(self.duration_ms)) as u8)
+    }
+
+    /// Combine the old and new frames into `out` at `progress` (0..=255)
+    ///
+    /// All three slices are expected to be the same length; extra LEDs in
+    /// any of them are left alone.
+    pub fn mix(&self, from: &[RGB8], to: &[RGB8], progress: u8, out: &mut [RGB8]) {
+        let led_qty = out.len().min(from.len()).min(to.len());
+        let pairs = from.iter().zip(to.iter()).take(led_qty);
+
+        match self.kind {
+            TransitionKind::Cut => out[..led_qty].copy_from_slice(&to[..led_qty]),
+            TransitionKind::Crossfade => {
+                for (o, (&f, &t)) in out.iter_mut().zip(pairs) {
+                    *o = blend(f, t, progress);
+                }
+            }
+            TransitionKind::Wipe => {
+                // Edge position in 1/256 of an LED, so the edge LED blends
+                let edge = usize::from(progress) * led_qty;
+                for (i, (o, (&f, &t))) in out.iter_mut().zip(pairs).enumerate() {
+                    let amount = edge.saturating_sub(i * 256).min(255) as u8;
+                    *o = blend(f, t, amount);
+                }
+            }
+            TransitionKind::FadeThroughBlack => {
+                let black = RGB8::default();
+                for (o, (&f, &t)) in out.iter_mut().zip(pairs) {
+                    *o = match progress {
+                        0..128 => blend(f, black, progress * 2),
+                        _ => blend(black, t, (progress - 128) * 2),
+                    };
+                }
+            }
+        }
+    }
+}
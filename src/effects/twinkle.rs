@@ -0,0 +1,63 @@
+use super::{Effect, Frame, fade_to_black};
+
+const FADE: u8 = 24; // Fraction of 256 lost per nominal frame
+const SPARK_CHANCE: u32 = 48; // Out of 256 per nominal frame
+
+/// Random LEDs lighting up and slowly fading out
+pub struct Twinkle;
+
+impl Effect for Twinkle {
+    fn name(&self) -> &'static str {
+        "Twinkle"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.leds.len() as u32;
+        if led_qty == 0 {
+            return;
+        }
+
+        fade_to_black(frame.leds, frame.per_frame_u8(FADE));
+
+        let chance = frame.per_frame(SPARK_CHANCE);
+        if u32::from(frame.rng.next_u8()) < chance {
+            let i = frame.rng.below(led_qty) as usize;
+            frame.leds[i] = frame.params.color;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner, lit};
+
+    #[test]
+    fn repeats_with_the_same_seed() {
+        let mut first = Runner::<12>::new(42);
+        let mut second = Runner::<12>::new(42);
+
+        assert_eq!(
+            first.run(&Twinkle, 20, 2000),
+            second.run(&Twinkle, 20, 2000)
+        );
+    }
+
+    #[test]
+    fn sparks_fade_in_the_effect_color() {
+        let mut runner = Runner::<12>::new(7);
+
+        let leds = runner.run(&Twinkle, 20, 2000);
+        assert!(lit(&leds) > 0);
+        assert!(leds.iter().all(|led| led.g == 0 && led.b == 0));
+        assert!(leds.iter().any(|led| led.r > 0 && led.r < COLOR.r));
+    }
+
+    #[test]
+    fn stopped_time_changes_nothing() {
+        let mut runner = Runner::<12>::new(7);
+
+        let leds = runner.run(&Twinkle, 20, 1000);
+        assert_eq!(runner.render(&Twinkle, 0), leds);
+    }
+}
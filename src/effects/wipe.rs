@@ -0,0 +1,53 @@
+use smart_leds::RGB8;
+
+use super::{Effect, Frame};
+
+const STEP_MS: u64 = 100;
+
+/// Ring filling with the color one LED at a time, then emptying the same way
+pub struct ColorWipe;
+
+impl Effect for ColorWipe {
+    fn name(&self) -> &'static str {
+        "Color Wipe"
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let led_qty = frame.leds.len();
+        if led_qty == 0 {
+            return;
+        }
+
+        let step = (frame.scaled_ms / STEP_MS) as usize % (led_qty * 2);
+        let (filled, empty) = if step < led_qty {
+            (frame.params.color, RGB8::default())
+        } else {
+            (RGB8::default(), frame.params.color)
+        };
+        let edge = step % led_qty;
+
+        for (i, led) in frame.leds.iter_mut().enumerate() {
+            *led = if i <= edge { filled } else { empty };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::testing::{COLOR, Runner, lit};
+
+    #[test]
+    fn fills_then_empties() {
+        let mut runner = Runner::<4>::new(1);
+
+        assert_eq!(lit(&runner.render(&ColorWipe, 0)), 1);
+        assert_eq!(runner.render(&ColorWipe, 200)[..3], [COLOR; 3]);
+        assert_eq!(runner.render(&ColorWipe, 100), [COLOR; 4]);
+
+        let leds = runner.render(&ColorWipe, 100); // Emptying from LED 0
+        assert_eq!(leds[0], RGB8::default());
+        assert_eq!(leds[1..], [COLOR; 3]);
+        assert_eq!(lit(&runner.render(&ColorWipe, 300)), 0);
+    }
+}
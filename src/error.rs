@@ -5,6 +5,7 @@ use stm32f4xx_hal;
 pub enum Error {
     I2C,
     SPI,
+    Flash,
     Generic,
 }
 
@@ -18,4 +19,10 @@ impl From <stm32f4xx_hal::spi::Error> for Error {
     fn from(_: stm32f4xx_hal::spi::Error) -> Self {
         Error::SPI
     }
+}
+
+impl From <stm32f4xx_hal::flash::Error> for Error {
+    fn from(_: stm32f4xx_hal::flash::Error) -> Self {
+        Error::Flash
+    }
 }
\ No newline at end of file
@@ -0,0 +1,61 @@
+/// Tachometer pulses per fan revolution, as on standard PC fans
+pub const TACH_PULSES_PER_REV: u32 = 2;
+
+/// Fan speed from the tachometer pulses counted over `window_ms`
+pub fn rpm(pulses: u32, window_ms: u32) -> u16 {
+    if window_ms == 0 {
+        return 0;
+    }
+
+    let rpm = u64::from(pulses) * 60_000 / (u64::from(window_ms) * u64::from(TACH_PULSES_PER_REV));
+    u16::try_from(rpm).unwrap_or(u16::MAX)
+}
+
+/// MCU die temperature in tenths of a degree Celsius from a 12-bit reading
+/// of the internal sensor
+///
+/// Linear through the factory readings at 30C and 110C, which are taken at
+/// 3.3V VDDA. The die runs a few degrees above the air around the board.
+pub fn die_temperature_deci_c(raw: u16, cal_30c: u16, cal_110c: u16) -> i16 {
+    let span = (i32::from(cal_110c) - i32::from(cal_30c)).max(1);
+    let deci_c = 300 + (i32::from(raw) - i32::from(cal_30c)) * 800 / span;
+
+    deci_c.clamp(i32::from(i16::MIN), i32::from(i16::MAX)) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpm_counts_two_pulses_per_turn() {
+        assert_eq!(rpm(0, 1000), 0);
+        assert_eq!(rpm(40, 1000), 1200);
+        assert_eq!(rpm(20, 500), 1200);
+        assert_eq!(rpm(41, 1000), 1230);
+    }
+
+    #[test]
+    fn rpm_survives_odd_windows() {
+        assert_eq!(rpm(40, 0), 0);
+        assert_eq!(rpm(u32::MAX, 1), u16::MAX);
+    }
+
+    // Typical calibration readings of an STM32F411
+    const CAL_30C: u16 = 940;
+    const CAL_110C: u16 = 1200;
+
+    #[test]
+    fn temperature_goes_through_calibration_points() {
+        assert_eq!(die_temperature_deci_c(CAL_30C, CAL_30C, CAL_110C), 300);
+        assert_eq!(die_temperature_deci_c(CAL_110C, CAL_30C, CAL_110C), 1100);
+        assert_eq!(die_temperature_deci_c(1070, CAL_30C, CAL_110C), 700);
+    }
+
+    #[test]
+    fn temperature_extends_past_calibration_points() {
+        assert_eq!(die_temperature_deci_c(924, CAL_30C, CAL_110C), 251);
+        assert_eq!(die_temperature_deci_c(0, CAL_30C, CAL_110C), -2592);
+        assert_eq!(die_temperature_deci_c(1000, 1000, 1000), 300);
+    }
+}
@@ -0,0 +1,74 @@
+use stm32f4xx_hal::pac;
+
+const SCL_PIN: u32 = 8; // PB8
+const SDA_PIN: u32 = 9; // PB9
+const HALF_PERIOD_CYCLES: u32 = 240; // 5us at 48MHz, a 100kHz bus clock
+
+const MODER_MASK: u32 = (0b11 << (SCL_PIN * 2)) | (0b11 << (SDA_PIN * 2));
+const MODER_OUTPUT: u32 = (0b01 << (SCL_PIN * 2)) | (0b01 << (SDA_PIN * 2));
+
+/// Free I2C1 (PB8 SCL, PB9 SDA) from a slave holding SDA low
+///
+/// A slave that lost its clock mid-byte keeps driving SDA until it has
+/// shifted the rest of the byte out. The pins are taken over as open-drain
+/// GPIO, SCL is pulsed until SDA is released (at most 9 clocks) and a STOP
+/// is sent by hand. The pins then go back to the peripheral, which gets a
+/// software reset with its timing registers restored.
+///
+/// Returns whether SDA is released afterwards.
+pub fn recover_i2c1() -> bool {
+    // SAFETY: only called with the display resource locked, which owns I2C1
+    // and its pins. GPIOB is changed through BSRR and masked MODER updates,
+    // so the other GPIOB pins are left alone.
+    let gpiob = unsafe { &*pac::GPIOB::ptr() };
+    let i2c = unsafe { &*pac::I2C1::ptr() };
+
+    let set_pin = |pin: u32, high: bool| {
+        let bit = if high { 1 << pin } else { 1 << (pin + 16) };
+        gpiob.bsrr.write(|w| unsafe { w.bits(bit) });
+        cortex_m::asm::delay(HALF_PERIOD_CYCLES);
+    };
+    let sda_high = || gpiob.idr.read().bits() & (1 << SDA_PIN) != 0;
+
+    // Lines are released before the pins leave alternate mode (OTYPER is
+    // already open-drain from the I2C setup)
+    set_pin(SCL_PIN, true);
+    set_pin(SDA_PIN, true);
+    let moder_af = gpiob.moder.read().bits() & MODER_MASK;
+    gpiob
+        .moder
+        .modify(|r, w| unsafe { w.bits((r.bits() & !MODER_MASK) | MODER_OUTPUT) });
+
+    for _ in 0..9 {
+        if sda_high() {
+            break;
+        }
+        set_pin(SCL_PIN, false);
+        set_pin(SCL_PIN, true);
+    }
+
+    // STOP: SDA rises while SCL is high
+    set_pin(SCL_PIN, false);
+    set_pin(SDA_PIN, false);
+    set_pin(SCL_PIN, true);
+    set_pin(SDA_PIN, true);
+    let released = sda_high();
+
+    gpiob
+        .moder
+        .modify(|r, w| unsafe { w.bits((r.bits() & !MODER_MASK) | moder_af) });
+
+    // Software reset clears BUSY and any half-finished transfer, but also the
+    // clock setup done by the HAL
+    let cr2 = i2c.cr2.read().bits();
+    let ccr = i2c.ccr.read().bits();
+    let trise = i2c.trise.read().bits();
+    i2c.cr1.write(|w| w.swrst().set_bit());
+    i2c.cr1.write(|w| w.swrst().clear_bit());
+    i2c.cr2.write(|w| unsafe { w.bits(cr2) });
+    i2c.ccr.write(|w| unsafe { w.bits(ccr) });
+    i2c.trise.write(|w| unsafe { w.bits(trise) });
+    i2c.cr1.write(|w| w.pe().set_bit());
+
+    released
+}
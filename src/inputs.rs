@@ -2,14 +2,19 @@ use stm32f4xx_hal::{
     adc,
     gpio::{EPin, Input},
     pac,
+    signature::{VtempCal30, VtempCal110},
 };
 
+use crate::fan_sensors;
+
+/// Digital input whose bounces are waited out rather than sampled away
+///
+/// Read it once the pin has held its level for the debounce delay; with an
+/// edge interrupt, that long after the last edge, so that every bounce
+/// pushes the read back.
 pub struct DebouncedDInput {
     pin: EPin<Input>,
-    delay_millis: u32,
     now_state: bool,
-    last_state: bool,
-    last_millis: u32, // This will now store time in milliseconds from AppMono
 }
 
 pub enum DebouncedOutput {
@@ -17,75 +22,68 @@ pub enum DebouncedOutput {
     Changed(bool),
 }
 
-pub struct PotRead {
+/// Pot on ADC1, which other analog inputs may share
+pub struct PotRead<PIN> {
     device: adc::Adc<pac::ADC1>,
+    pin: PIN,
+}
+
+/// Latest readings of the slow analog inputs, taken by the microphone
+/// sampler between its samples
+#[derive(Clone, Copy, Default)]
+pub struct AnalogReadings {
+    pub pot_percent: u16,
+    /// LDR counts; `None` without one
+    pub light: Option<u16>,
+    /// MCU die temperature in tenths of a degree Celsius
+    pub temperature_deci_c: i16,
 }
 
 impl DebouncedDInput {
     pub fn with_pullup(pin: EPin<Input>) -> Self {
         Self {
             pin: pin,
-            delay_millis: 10, // Debounce delay in ms
-            now_state: true,  // Assuming initial state is high due to pull-up
-            last_state: true,
-            last_millis: 0, // Initialized to 0
+            now_state: true, // Assuming initial state is high due to pull-up
         }
     }
 
-    pub fn is_low(&mut self, current_time_ms: u32) -> DebouncedOutput {
-        match self.read(current_time_ms) {
+    pub fn is_low(&mut self) -> DebouncedOutput {
+        match self.read() {
             DebouncedOutput::Constant(v) => DebouncedOutput::Constant(!v),
             DebouncedOutput::Changed(v) => DebouncedOutput::Changed(!v),
         }
     }
 
-    pub fn is_high(&mut self, current_time_ms: u32) -> DebouncedOutput {
-        self.read(current_time_ms)
+    pub fn is_high(&mut self) -> DebouncedOutput {
+        self.read()
     }
 
-    fn read(&mut self, current_time_ms: u32) -> DebouncedOutput {
+    fn read(&mut self) -> DebouncedOutput {
         let is_pin_high = self.pin.is_high();
-        let mut ans = DebouncedOutput::Constant(self.now_state);
-
-        if is_pin_high != self.last_state {
-            self.last_millis = current_time_ms; // Update with current time from AppMono
+        if is_pin_high == self.now_state {
+            return DebouncedOutput::Constant(self.now_state);
         }
 
-        if current_time_ms.saturating_sub(self.last_millis) > self.delay_millis
-            && is_pin_high != self.now_state
-        {
-            self.now_state = is_pin_high;
-            ans = DebouncedOutput::Changed(self.now_state);
-        }
-
-        self.last_state = is_pin_high;
-        ans
+        self.now_state = is_pin_high;
+        DebouncedOutput::Changed(self.now_state)
     }
 }
 
-impl PotRead {
-    pub fn with_adc01(
-        pin: impl embedded_hal::adc::Channel<pac::ADC1, ID = u8>,
-        adc: pac::ADC1,
-    ) -> Self {
+impl<PIN: embedded_hal::adc::Channel<pac::ADC1, ID = u8>> PotRead<PIN> {
+    pub fn with_adc01(pin: PIN, adc: pac::ADC1) -> Self {
         // Input analog pot read
         let mut adc01 = adc::Adc::adc1(adc, true, adc::config::AdcConfig::default());
-        adc01.configure_channel(
-            &pin,
-            adc::config::Sequence::One,
-            adc::config::SampleTime::Cycles_480,
-        );
+        adc01.enable_temperature_and_vref();
+        adc01.enable();
 
-        Self { device: adc01 }
+        Self { device: adc01, pin }
     }
 
     pub fn read_percent(&mut self) -> u16 {
-        if !self.device.is_enabled() {
-            self.device.enable();
-        }
-
-        self.device.start_conversion();
-        let sample = self.device.current_sample();
+        // Converting selects the channel again, in case another input was read
+        let sample = self
+            .device
+            .convert(&self.pin, adc::config::SampleTime::Cycles_480);
         let percent = u32::from(sample)
             .saturating_mul(100)
             .saturating_div(1u32 << 12)
@@ -93,4 +91,27 @@ impl PotRead {
 
         u16::try_from(percent).unwrap()
     }
+
+    /// MCU die temperature in tenths of a degree Celsius
+    ///
+    /// The sensor needs a sample time of over 10us, like the pot.
+    pub fn read_temperature_deci_c(&mut self) -> i16 {
+        let sample = self
+            .device
+            .convert(&adc::Temperature, adc::config::SampleTime::Cycles_480);
+
+        fan_sensors::die_temperature_deci_c(
+            sample,
+            VtempCal30::get().read(),
+            VtempCal110::get().read(),
+        )
+    }
+
+    /// One 12-bit sample of another input on the same ADC
+    ///
+    /// Takes a short sample time, a few microseconds, so that it can be
+    /// called from a sampling interrupt.
+    pub fn sample<P: embedded_hal::adc::Channel<pac::ADC1, ID = u8>>(&mut self, pin: &P) -> u16 {
+        self.device.convert(pin, adc::config::SampleTime::Cycles_56)
+    }
 }
@@ -1,8 +1,12 @@
-use embedded_hal::blocking::delay;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_hal::blocking::{delay, i2c::Write};
 use stm32f4xx_hal::i2c::{self, I2c};
 
 use hd44780_driver::{Cursor, CursorBlink, Display, DisplayMode, HD44780, bus::I2CBus};
 
+use crate::display;
+
 // Dummy delay provider using cortex_m::asm::delay
 struct AsmDelay;
 
@@ -26,11 +30,71 @@ impl delay::DelayMs<u8> for AsmDelay {
     }
 }
 
+pub const LCD_COLUMNS: usize = 16;
+const LCD_ROW_OFFSET: u8 = 0x40; // DDRAM address of the second row
+
+// PCF8574 pin P3 drives the backlight transistor on the backpack
+const PCF8574_BACKLIGHT: u8 = 0b0000_1000;
+// The transistor only switches, so dim levels below this turn it off
+const BACKLIGHT_MIN_LEVEL: u8 = 32;
+
+// The HD44780 driver owns the bus and always sets the backlight bit, so the
+// wanted state lives here and is masked into every byte on the way out.
+static BACKLIGHT_ON: AtomicBool = AtomicBool::new(true);
+
+// Set when a write to the backpack fails, cleared when `take_bus_fault` reports it
+static BUS_FAULT: AtomicBool = AtomicBool::new(false);
+
+/// I2C wrapper for the PCF8574 backpack
+///
+/// Applies the backlight state to every write and records failed writes in
+/// `BUS_FAULT` instead of returning them. The driver drops its bus when
+/// construction fails, so this keeps the LCD object alive with the panel
+/// unplugged and lets it be brought back later.
+pub struct BackpackI2c<I2C> {
+    bus: I2C,
+}
+
+impl<I2C> Write for BackpackI2c<I2C>
+where
+    I2C: Write,
+{
+    type Error = I2C::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        let backlight_mask = if BACKLIGHT_ON.load(Ordering::Relaxed) {
+            0xFF
+        } else {
+            !PCF8574_BACKLIGHT
+        };
+
+        for byte in bytes {
+            if self.bus.write(address, &[byte & backlight_mask]).is_err() {
+                BUS_FAULT.store(true, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn take_bus_fault() -> Result<(), crate::error::Error> {
+    if BUS_FAULT.swap(false, Ordering::Relaxed) {
+        return Err(crate::error::Error::I2C);
+    }
+
+    Ok(())
+}
+
 pub struct I2CLcd<I2C>
 where
     I2C: i2c::Instance,
 {
-    device: HD44780<I2CBus<I2c<I2C>>>,
+    device: HD44780<I2CBus<BackpackI2c<I2c<I2C>>>>,
+    backlight_timeout_secs: u32, // 0 keeps the backlight on
+    backlight_on: bool,
+    backlight_level: u8,
 }
 
 impl<'a, I2C, Delay> I2CLcd<I2C>
@@ -42,102 +106,187 @@ where
         i2c_bus: I2c<I2C>,
         delay_for_init: &'a mut Delay,
     ) -> Result<Self, crate::error::Error> {
-        let lcd_1602: HD44780<I2CBus<I2c<I2C>>> = HD44780::new_i2c(i2c_bus, 0x27, delay_for_init)?;
+        BACKLIGHT_ON.store(true, Ordering::Relaxed);
+        BUS_FAULT.store(false, Ordering::Relaxed);
+        let lcd_1602: HD44780<I2CBus<BackpackI2c<I2c<I2C>>>> =
+            HD44780::new_i2c(BackpackI2c { bus: i2c_bus }, 0x27, delay_for_init)?;
 
-        Ok(Self { device: lcd_1602 })
+        Ok(Self {
+            device: lcd_1602,
+            backlight_timeout_secs: 0,
+            backlight_on: true,
+            backlight_level: 255,
+        })
     }
 
     pub fn init(&mut self, delay_for_init: &'a mut Delay) -> Result<(), crate::error::Error> {
         self.device.reset(delay_for_init)?;
         self.device.clear(delay_for_init)?;
 
-        self.device.set_display_mode(
-            DisplayMode {
-                display: Display::On,
-                cursor_visibility: Cursor::Invisible,
-                cursor_blink: CursorBlink::Off,
-            },
-            delay_for_init,
-        )?;
+        self.device
+            .set_display_mode(Self::display_mode(), delay_for_init)?;
 
-        Ok(())
+        // Also reports a panel that was missing when `new` ran
+        take_bus_fault()
     }
 
-    pub fn write_duty_cycle(&mut self, mut duty_cycle: u8) -> Result<(), crate::error::Error> {
+    pub fn write_message(
+        &mut self,
+        message: &str,
+        position: (u8, u8),
+    ) -> Result<(), crate::error::Error> {
         let mut asm_delay = AsmDelay;
-        duty_cycle = duty_cycle.clamp(0, 100);
-        let (duty_cycle_num_start, duty_cycle_bytes) = Self::from_number(u32::from(duty_cycle));
-
         self.device
-            .set_cursor_pos(0, &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .write_str("Fan:     ", &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .set_cursor_pos(5, &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .write_bytes(&duty_cycle_bytes[duty_cycle_num_start..], &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .write_str("%", &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
+            .set_cursor_pos(position.0 * LCD_ROW_OFFSET + position.1, &mut asm_delay)?;
+        self.device.write_str(message, &mut asm_delay)?;
 
-        Ok(())
+        take_bus_fault()
     }
 
-    pub fn write_number(&mut self, number: u32) -> Result<(), crate::error::Error> {
+    /// Switch the backlight as set and dimmed
+    fn apply_backlight(&mut self) -> Result<(), crate::error::Error> {
         let mut asm_delay = AsmDelay;
-        self.device
-            .set_cursor_pos(0, &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .write_str("DNum: ", &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
+        let on = self.backlight_on && self.backlight_level >= BACKLIGHT_MIN_LEVEL;
+        BACKLIGHT_ON.store(on, Ordering::Relaxed);
 
-        let (num_bytes_start, num_bytes) = Self::from_number(number);
+        // Any bus write latches the new backlight bit into the PCF8574
         self.device
-            .write_bytes(&num_bytes[num_bytes_start..], &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
+            .set_display_mode(Self::display_mode(), &mut asm_delay)?;
 
-        Ok(())
+        take_bus_fault()
     }
 
-    pub fn write_message(
-        &mut self,
-        message: &str,
-        position: (u8, u8),
-    ) -> Result<(), crate::error::Error> {
+    fn display_mode() -> DisplayMode {
+        DisplayMode {
+            display: Display::On,
+            cursor_visibility: Cursor::Invisible,
+            cursor_blink: CursorBlink::Off,
+        }
+    }
+}
+
+impl<I2C> display::Display for I2CLcd<I2C>
+where
+    I2C: i2c::Instance,
+{
+    const ROWS: u8 = 2;
+    const COLUMNS: usize = LCD_COLUMNS;
+
+    fn write_at(&mut self, position: (u8, u8), text: &str) -> Result<(), crate::error::Error> {
+        self.write_message(text, position)
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), crate::error::Error> {
+        self.backlight_on = on;
+        self.apply_backlight()
+    }
+
+    fn set_backlight_level(&mut self, level: u8) -> Result<(), crate::error::Error> {
+        self.backlight_level = level;
+        self.apply_backlight()
+    }
+
+    fn reinit(&mut self) -> Result<(), crate::error::Error> {
         let mut asm_delay = AsmDelay;
-        self.device
-            .set_cursor_pos(position.0 * 40 + position.1, &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
-        self.device
-            .write_str(message, &mut asm_delay)
-            .map_err(|_| crate::error::Error::LcdError)?;
+        self.init(&mut asm_delay)
+    }
 
-        Ok(())
+    fn set_backlight_timeout(&mut self, secs: u32) {
+        self.backlight_timeout_secs = secs;
     }
 
-    fn from_number(mut number: u32) -> (usize, [u8; 10]) {
-        let mut ans = [0u8; 10];
-        let mut ans_start = 10usize;
+    fn backlight_timeout(&self) -> Option<u32> {
+        match self.backlight_timeout_secs {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+}
 
-        ans[9] = b'0';
+#[derive(Clone, Copy)]
+enum MarqueeState {
+    PauseStart,
+    Scrolling,
+    PauseEnd,
+}
 
-        for v in ans.iter_mut().rev() {
-            if number <= 0 {
-                break;
-            }
+/// Row-wide text that scrolls when it does not fit on the display
+///
+/// The text is held at the start for `pause_ms`, shifted one column every
+/// `step_ms` until its end is visible, held again and then snapped back.
+/// `advance` is meant to be called from a periodic task; it never blocks.
+#[derive(Clone, Copy)]
+pub struct Marquee {
+    text: &'static str,
+    width: usize,
+    offset: usize,
+    state: MarqueeState,
+    step_ms: u32,
+    pause_ms: u32,
+    last_change_ms: u32,
+}
+
+impl Marquee {
+    pub fn new(width: usize, step_ms: u32, pause_ms: u32) -> Self {
+        Self {
+            text: "",
+            width,
+            offset: 0,
+            state: MarqueeState::PauseStart,
+            step_ms: step_ms.max(1),
+            pause_ms,
+            last_change_ms: 0,
+        }
+    }
 
-            *v = char::from_digit(u32::from(number % 10), 10)
-                .map(|v| u8::try_from(v).unwrap())
-                .unwrap();
-            number /= 10;
-            ans_start -= 1;
+    /// Show new text from its first column
+    pub fn set_text(&mut self, text: &'static str, current_time_ms: u32) {
+        self.text = text;
+        self.offset = 0;
+        self.state = MarqueeState::PauseStart;
+        self.last_change_ms = current_time_ms;
+    }
+
+    /// Move the marquee along
+    ///
+    /// Returns `true` when the visible window changed and should be redrawn.
+    pub fn advance(&mut self, current_time_ms: u32) -> bool {
+        let max_offset = self.text.len().saturating_sub(self.width);
+        if max_offset == 0 {
+            return false;
+        }
+
+        let elapsed = current_time_ms.wrapping_sub(self.last_change_ms);
+        let wait_ms = match self.state {
+            MarqueeState::Scrolling => self.step_ms,
+            _ => self.pause_ms,
+        };
+        if elapsed < wait_ms {
+            return false;
+        }
+
+        self.last_change_ms = current_time_ms;
+        match self.state {
+            MarqueeState::PauseEnd => {
+                self.offset = 0;
+                self.state = MarqueeState::PauseStart;
+            }
+            _ => {
+                self.offset += 1;
+                self.state = if self.offset >= max_offset {
+                    MarqueeState::PauseEnd
+                } else {
+                    MarqueeState::Scrolling
+                };
+            }
         }
 
-        (ans_start, ans)
+        true
+    }
+
+    /// Visible part of the text, at most one row wide
+    pub fn window(&self) -> &'static str {
+        let end = self.text.len().min(self.offset + self.width);
+        self.text.get(self.offset..end).unwrap_or("")
     }
 }
@@ -1,39 +1,137 @@
 // #![allow(clippy::empty_loop)]
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main)]
+#![cfg_attr(not(test), no_std)] // Host tests of the hardware-free modules run with std
 
+#[cfg(not(test))]
 use panic_halt as _;
 use rtic::app;
 
+mod adalight;
+mod ambient;
+mod audio;
+#[cfg(feature = "bh1750")]
+mod bh1750;
+mod commands;
+mod config;
+mod config_store;
+mod correction;
+mod display;
+mod dither;
+mod effects;
 mod error;
+mod fan_sensors;
+mod i2c_recovery;
 mod inputs;
 mod lcd;
+#[cfg(feature = "oled")]
+mod oled;
+mod power;
 mod pwm_fan;
+mod sequencer;
 mod stoptimer; // May become partially or fully unused
+mod strip;
+mod strip_dma;
+mod text;
+mod zone;
 
 #[cfg(use_defmt)]
 use defmt_rtt as _; // global logger
 
-#[app(device = stm32f4xx_hal::pac, peripherals = true, dispatchers = [TIM2, TIM4, SPI1])] // Added some dispatchers, adjust as needed
+#[app(device = stm32f4xx_hal::pac, peripherals = true, dispatchers = [TIM2, TIM4, SPI3])] // Interrupts no driver enables; not SPI1 or SPI2, which drive the strips
 mod app {
+    use crate::adalight;
+    use crate::ambient::{AmbientLight, BrightnessCurve};
+    use crate::audio::{self, Analyzer, SampleBlock};
+    use crate::commands::{self, Command, DirectFill, StripId, StripSetting, ZoneSetting};
+    use crate::config::Config;
+    use crate::config_store::ConfigStore;
+    use crate::correction::{ColorCorrection, WHITE_POINT_TYPICAL};
+    use crate::display::{Display, FaultTolerant, History, show_fan, show_history, show_marquee};
+    use crate::effects::{self, clock::LightingClock, sound::AudioLevels};
+    use crate::fan_sensors;
     use crate::hal::{
         self as hal, // alias hal for clarity within app mod
-        gpio::{self, Alternate, Analog, Input, NoPin, PullUp},
+        dma::{Stream3, Stream4, StreamsTuple},
+        flash::LockedFlash,
+        gpio::{self, Alternate, Analog, NoPin},
         i2c::{I2c, Mode},
         pac,
         prelude::*,
         rcc,
-        spi,
-        timer::{self, Event, MonoTimerUs},
+        serial,
+        timer::{self, CounterHz, Event, Flag, MonoTimerUs},
     };
-    use crate::inputs::{DebouncedDInput, DebouncedOutput, PotRead};
+    use crate::i2c_recovery;
+    use crate::inputs::{AnalogReadings, DebouncedDInput, DebouncedOutput, PotRead};
     use crate::lcd;
+    #[cfg(feature = "oled")]
+    use crate::oled;
+    use crate::power::PowerBudget;
     use crate::pwm_fan;
+    use crate::sequencer::Sequencer;
+    use crate::strip::{FRAME_BYTES, FrameBuffer, StripConfig, StripOutput};
+    use crate::strip_dma::DmaStrip;
+    use crate::zone::{MAX_ZONES, Zone};
     // use crate::stoptimer; // stoptimer module is now mostly empty
 
+    use core::fmt::Write;
     use cortex_m::peripheral::SYST;
     use defmt;
 
+    #[cfg(not(feature = "oled"))]
+    type AppDisplay = lcd::I2CLcd<pac::I2C1>;
+    #[cfg(feature = "oled")]
+    type AppDisplay = oled::I2COled<pac::I2C1>;
+
+    const DISPLAY_BACKLIGHT_TIMEOUT_SECS: u32 = 30; // 0 keeps the backlight on
+    const DISPLAY_RETRY_SECS: u32 = 2; // Re-init attempt period while offline
+    const DUTY_HISTORY_LEN: usize = 64; // One sample per second
+    const MARQUEE_STEP_MS: u32 = 350; // Time per scrolled column
+    const MARQUEE_PAUSE_MS: u32 = 1500; // Hold time at either end
+    const BUTTON_DEBOUNCE_MS: u32 = 20; // Level the button has to hold before it counts
+    const BRIGHTNESS_HOLD_MS: u32 = 600; // Longer presses step the brightness
+    const POWER_HOLD_MS: u32 = 2000; // Longer still turn the LEDs on or off
+    const ADALIGHT_BAUD: u32 = 115_200;
+    const ADALIGHT_TIMEOUT_MS: u32 = 2000; // Host silence before the effect comes back
+    const RGB_UPDATE_MS: u32 = 10; // Fast enough for temporal dithering not to flicker; effects are drawn every other frame
+    const FAN_ZONES: &[Zone] = &[]; // E.g. &[Zone::new("Fan 1", 0, 4), Zone::new("Fan 2", 4, 4).reversed()]
+    const FAN_STRIP: StripConfig = StripConfig::new(8) // SPI2, the fan ring
+        .with_zones(FAN_ZONES)
+        .with_power_budget(PowerBudget::new(300)) // Both strips together stay within a USB port's 500mA
+        .with_correction(ColorCorrection::new().with_white_point(WHITE_POINT_TYPICAL));
+    const AUX_STRIP: StripConfig = StripConfig::rgbw(12) // SPI1, SK6812 RGBW
+        .with_effect("Breathe")
+        .with_power_budget(PowerBudget::new(150));
+
+    type FanStripOutput = DmaStrip<Stream4<pac::DMA1>, 0, pac::SPI2>; // SPI2_TX
+    type AuxStripOutput = DmaStrip<Stream3<pac::DMA2>, 3, pac::SPI1>; // SPI1_TX
+
+    const FAN_TACH: bool = true; // PB0, pulled up; false for fans without a tach wire
+    const FAN_SENSOR_READ_MS: u32 = 1000; // Tach pulses are counted over this long
+    const AMBIENT_READ_MS: u32 = 200;
+    const SLOW_INPUT_SAMPLES: u32 = audio::SAMPLE_RATE_HZ / 20; // Pot and LDR read every 50ms
+    const AMBIENT_SMOOTHING_SHIFT: u32 = 4; // About 3s to follow a change
+    // Room light to LED and display brightness; the LED one scales the
+    // brightness set with the button. An HD44780 cannot dim, so its
+    // backlight goes off where the display curve is below 32.
+    #[cfg(not(feature = "bh1750"))]
+    const LED_AMBIENT_CURVE: BrightnessCurve =
+        BrightnessCurve::new(&[(100, 48), (1000, 128), (3000, 255)]); // LDR ADC counts
+    #[cfg(not(feature = "bh1750"))]
+    const DISPLAY_AMBIENT_CURVE: BrightnessCurve =
+        BrightnessCurve::new(&[(100, 16), (1000, 96), (3000, 255)]);
+    #[cfg(feature = "bh1750")]
+    const LED_AMBIENT_CURVE: BrightnessCurve =
+        BrightnessCurve::new(&[(5, 48), (100, 128), (1000, 255)]); // Lux
+    #[cfg(feature = "bh1750")]
+    const DISPLAY_AMBIENT_CURVE: BrightnessCurve =
+        BrightnessCurve::new(&[(5, 16), (100, 96), (1000, 255)]);
+
+    #[cfg(not(feature = "bh1750"))]
+    type LightSensor = (); // The LDR is read by `sample_mic`
+    #[cfg(feature = "bh1750")]
+    type LightSensor = bh1750::Bh1750<I2c<pac::I2C3>>;
+
     // Define a monotonic timer based on TIM3
     #[monotonic(binds = TIM3, default = true)]
     type AppMono = MonoTimerUs<pac::TIM3>;
@@ -43,20 +141,37 @@ mod app {
         pwm_obj: pwm_fan::AdjustablePwmFan<
             'static,
             pac::TIM2,
-            pac::SPI2,
+            FanStripOutput,
             timer::Channel3,
             gpio::PB10<Alternate>,
             NoPin,
             gpio::PB15<Alternate>,
         >,
-        lcd: lcd::I2CLcd<pac::I2C1>, // Corrected type: Removed 'static and Delay type parameter
-        rgb_needs_lcd_update: bool, // Flag to signal LCD update for RGB mode
+        aux_rgb: pwm_fan::PwmFanRgb<AuxStripOutput>, // Second strip with its own effect
+        lighting_clock: LightingClock, // 64-bit time for the strips, read on every RGB update
+        config: Config,                // Settings as edited, saved on command
+        sequencer: Sequencer,          // Plays scenes on the fan strip
+        display: FaultTolerant<AppDisplay>,
+        rgb_needs_display_update: bool, // Flag to signal display update for RGB mode
+        mode_marquee: lcd::Marquee,     // Scrolls the RGB mode name under the duty cycle
+        duty_history: History<DUTY_HISTORY_LEN>,
+        analog_readings: AnalogReadings, // Pot, LDR and temperature, read by `sample_mic`
+        tach_pulses: u32,                // Counted since `read_fan_sensors` last took them
+        audio_levels: AudioLevels,       // Latest analysis, for the "Sound" effect
     }
 
     #[local]
     struct Local {
-        pot_obj: PotRead<'static, pac::ADC1, gpio::PA7<Analog>>,
-        user_button: DebouncedDInput<'static, gpio::PC13<Input<PullUp>>>,
+        pot_obj: PotRead<gpio::PA7<Analog>>, // Owns ADC1, which the microphone is sampled on
+        mic: gpio::PA0<Analog>,
+        ldr: Option<gpio::PA1<Analog>>, // LDR to 3.3V, 10k to ground; None with the BH1750
+        mic_timer: CounterHz<pac::TIM5>, // Paces the microphone samples
+        user_button: DebouncedDInput,
+        tach_pin: gpio::PB0<gpio::Input>,
+        host_rx: serial::Rx<pac::USART2>, // Adalight frames from the PC
+        host_tx: serial::Tx<pac::USART2>, // Command replies
+        config_store: ConfigStore,
+        light_sensor: LightSensor,
         general_delay: hal::timer::Delay<SYST, 1_000_000_u32>, // For one-off delays if needed, though tasks are preferred
     }
 
@@ -64,6 +179,21 @@ mod app {
         rcc_dp.cfgr.sysclk(48.MHz()).freeze()
     }
 
+    /// Apply a `strip` command setting to one strip's color correction;
+    /// returns the correction, to be saved
+    fn change_correction<OUT: StripOutput>(
+        rgb_obj: &mut pwm_fan::PwmFanRgb<OUT>,
+        setting: StripSetting,
+    ) -> ColorCorrection {
+        let mut correction = rgb_obj.config().correction;
+        match setting {
+            StripSetting::Gamma(gamma) => correction.gamma = gamma,
+            StripSetting::WhitePoint(white_point) => correction.white_point = white_point,
+        }
+        rgb_obj.set_correction(correction);
+        correction
+    }
+
     #[init]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("RTIC Init!\n");
@@ -88,9 +218,40 @@ mod app {
 
         // Pot
         let pa7_analog = gpioa.pa7.into_analog();
-        let pot_obj = PotRead::with_adc01(pa7_analog, dp.ADC1);
+        let mut pot_obj = PotRead::with_adc01(pa7_analog, dp.ADC1);
         defmt::info!("Potentiometer initialized.");
 
+        // Microphone, sampled on the pot's ADC
+        let mic = gpioa.pa0.into_analog();
+        let mut mic_timer = dp.TIM5.counter_hz(&clocks);
+        mic_timer.start(audio::SAMPLE_RATE_HZ.Hz()).unwrap();
+        mic_timer.listen(Event::Update);
+        defmt::info!("Microphone initialized.");
+
+        // Ambient light sensor
+        #[cfg(not(feature = "bh1750"))]
+        let (ldr, light_sensor) = (Some(gpioa.pa1.into_analog()), ()); // Read on the pot's ADC
+        #[cfg(feature = "bh1750")]
+        let ldr = None;
+        #[cfg(feature = "bh1750")]
+        let light_sensor = bh1750::Bh1750::new(I2c::new(
+            dp.I2C3,
+            (
+                gpioa.pa8.into_alternate_open_drain::<4>(),
+                gpiob.pb4.into_alternate_open_drain::<9>(),
+            ),
+            Mode::standard(100.kHz()),
+            &clocks,
+        ));
+        defmt::info!("Light sensor initialized.");
+
+        // First readings, until the sampler takes over
+        let analog_readings = AnalogReadings {
+            pot_percent: pot_obj.read_percent(),
+            light: ldr.as_ref().map(|ldr| pot_obj.sample(ldr)),
+            temperature_deci_c: pot_obj.read_temperature_deci_c(),
+        };
+
         // User button (PC13)
         // Configure PC13 for EXTI interrupt
         let mut syscfg = dp.SYSCFG.constrain();
@@ -98,10 +259,17 @@ mod app {
         let user_button_pin = gpioc.pc13.into_pull_up_input();
         user_button_pin.make_interrupt_source(&mut syscfg);
         user_button_pin.enable_interrupt(&mut exti);
-        user_button_pin.trigger_on_edge(&mut exti, gpio::Edge::Falling); // Trigger on press (assuming pull-up means low when pressed)
+        user_button_pin.trigger_on_edge(&mut exti, gpio::Edge::RisingFalling); // Press and release, to tell long presses apart
         let user_button = DebouncedDInput::with_pullup(user_button_pin.erase());
         defmt::info!("User button PC13 initialized for EXTI.");
 
+        // Fan tachometer (PB0), pulled low twice per turn by the fan
+        let mut tach_pin = gpiob.pb0.into_pull_up_input();
+        tach_pin.make_interrupt_source(&mut syscfg);
+        tach_pin.enable_interrupt(&mut exti);
+        tach_pin.trigger_on_edge(&mut exti, gpio::Edge::Falling);
+        defmt::info!("Fan tachometer PB0 initialized for EXTI.");
+
         // RGB fan
         // Ensure correct Alternate Function (AF) mapping for your specific STM32F411.
         // PB13 (SPI2_SCK), PB15 (SPI2_MOSI)
@@ -117,71 +285,177 @@ mod app {
             3.MHz(),
             &clocks,
         );
+        // Frames are sent by DMA from two buffers each, see `DmaStrip`
+        let dma1 = StreamsTuple::new(dp.DMA1);
+        let dma2 = StreamsTuple::new(dp.DMA2);
+        // One static per `singleton!` site, so each buffer needs its own
+        let fan_buffers = (
+            cortex_m::singleton!(: FrameBuffer = [0u8; FRAME_BYTES]).unwrap(),
+            cortex_m::singleton!(: FrameBuffer = [0u8; FRAME_BYTES]).unwrap(),
+        );
+        let aux_buffers = (
+            cortex_m::singleton!(: FrameBuffer = [0u8; FRAME_BYTES]).unwrap(),
+            cortex_m::singleton!(: FrameBuffer = [0u8; FRAME_BYTES]).unwrap(),
+        );
+        let fan_output = DmaStrip::new(dma1.4, spi02.use_dma().tx(), fan_buffers.0, fan_buffers.1);
+
         let pwm_pin_d6 = gpiob.pb10.into_alternate::<1>(); // AF1 for TIM2_CH3 on PB10
         let fan_pwm_channel = timer::Channel3::new(pwm_pin_d6);
         let mut pwm_obj = pwm_fan::AdjustablePwmFan::with_rgb(
             dp.TIM2,
             fan_pwm_channel,
             timer::Channel::C3, // This argument seems redundant if Channel3::new is used, check pwm_fan module
-            spi02,
+            fan_output,
+            FAN_STRIP,
             &clocks,
         );
         pwm_obj.init();
         pwm_obj.set_duty(50); // Initial duty
         defmt::info!("PWM Fan initialized.");
 
-        // LCD
+        // Second strip: PB3 (SPI1_SCK), PB5 (SPI1_MOSI), both AF5
+        let spi01 = dp.SPI1.spi(
+            (
+                gpiob.pb3.into_alternate::<5>(),
+                NoPin::new(),
+                gpiob.pb5.into_alternate::<5>(),
+            ),
+            ws2812_spi::MODE,
+            3.MHz(),
+            &clocks,
+        );
+        let aux_output = DmaStrip::new(dma2.3, spi01.use_dma().tx(), aux_buffers.0, aux_buffers.1);
+        let mut aux_rgb = pwm_fan::PwmFanRgb::new(aux_output, AUX_STRIP);
+        defmt::info!("Aux strip initialized.");
+
+        // Host serial: PA2 (USART2_TX), PA3 (USART2_RX), both AF7, which the
+        // Nucleo board routes to the ST-LINK virtual COM port
+        let host_serial = dp
+            .USART2
+            .serial(
+                (
+                    gpioa.pa2.into_alternate::<7>(),
+                    gpioa.pa3.into_alternate::<7>(),
+                ),
+                serial::Config::default().baudrate(ADALIGHT_BAUD.bps()),
+                &clocks,
+            )
+            .unwrap();
+        let (mut host_tx, mut host_rx) = host_serial.split();
+        for &byte in adalight::HELLO {
+            nb::block!(host_tx.write(byte)).ok();
+        }
+        host_rx.listen();
+        defmt::info!("Host serial initialized.");
+
+        // Settings saved in flash; defaults if there are none yet
+        let config_store = ConfigStore::new(LockedFlash::new(dp.FLASH));
+        let config = config_store.load().unwrap_or_default();
+        let mut sequencer = Sequencer::new();
+        if let Some(playlist) = config.autoplay {
+            sequencer.start(playlist);
+        }
+        if let Some(rgb_obj) = &mut pwm_obj.rgb {
+            rgb_obj.set_user_colors(config.user);
+            if let Some(correction) = config.fan_correction {
+                rgb_obj.set_correction(correction);
+            }
+        }
+        aux_rgb.set_user_colors(config.user);
+        if let Some(correction) = config.aux_correction {
+            aux_rgb.set_correction(correction);
+        }
+        defmt::info!("Config loaded.");
+
+        // Display
         // For STM32F411: PB8 (I2C1_SCL), PB9 (I2C1_SDA) are AF4
         let i2c_scl = gpiob.pb8.into_alternate_open_drain::<4>();
         let i2c_sda = gpiob.pb9.into_alternate_open_drain::<4>();
 
-        let i2c_01 = I2c::new(
-            dp.I2C1,
-            (i2c_scl, i2c_sda),
-            Mode::standard(100.kHz()),
-            &clocks,
-        );
-        let mut lcd_obj = lcd::I2CLcd::new(i2c_01, &mut lcd_init_delay_timer).unwrap();
-        lcd_obj.init(&mut lcd_init_delay_timer).unwrap(); // Pass delay again for init
-        lcd_obj.write_duty_cycle(50).unwrap(); // Initial duty on LCD
-        defmt::info!("LCD initialized.");
+        #[cfg(not(feature = "oled"))]
+        let i2c_mode = Mode::standard(100.kHz());
+        #[cfg(feature = "oled")]
+        let i2c_mode = Mode::fast(400.kHz(), hal::i2c::DutyCycle::Ratio2to1); // Frame buffer flushes
+
+        let i2c_01 = I2c::new(dp.I2C1, (i2c_scl, i2c_sda), i2c_mode, &clocks);
+        // Bus faults are recorded by the LCD, not returned, so this cannot fail
+        #[cfg(not(feature = "oled"))]
+        let display_dev = lcd::I2CLcd::new(i2c_01, &mut lcd_init_delay_timer).unwrap();
+        #[cfg(feature = "oled")]
+        let display_dev = oled::I2COled::new(i2c_01);
+
+        // Starts offline; `display_recovery` initializes and draws it
+        let mut display_obj = FaultTolerant::new(display_dev);
+        display_obj
+            .device_mut()
+            .set_backlight_timeout(DISPLAY_BACKLIGHT_TIMEOUT_SECS);
+        defmt::info!("Display created.");
 
         // Schedule initial tasks
         // Using `unwrap` for spawn as failure here is catastrophic
         read_pot_and_update_fan::spawn().unwrap();
         periodic_rgb_update::spawn().unwrap();
+        display_wake::spawn().unwrap(); // Arms the backlight timeout
+        display_marquee_update::spawn().unwrap();
+        sample_duty_history::spawn().unwrap();
+        read_ambient_light::spawn().unwrap();
+        read_fan_sensors::spawn().unwrap();
+        display_recovery::spawn().unwrap();
         defmt::info!("Initial tasks spawned.");
 
         (
             Shared {
                 pwm_obj,
-                lcd: lcd_obj,
-                rgb_needs_lcd_update: true,
+                aux_rgb,
+                lighting_clock: LightingClock::new(),
+                config,
+                sequencer,
+                display: display_obj,
+                rgb_needs_display_update: true,
+                mode_marquee: lcd::Marquee::new(
+                    AppDisplay::COLUMNS,
+                    MARQUEE_STEP_MS,
+                    MARQUEE_PAUSE_MS,
+                ),
+                duty_history: History::new(),
+                analog_readings,
+                tach_pulses: 0,
+                audio_levels: AudioLevels::default(),
             }, // Initially true to print mode
             Local {
                 pot_obj,
+                mic,
+                ldr,
+                mic_timer,
                 user_button,
+                tach_pin,
+                light_sensor,
                 general_delay,
+                host_rx,
+                host_tx,
+                config_store,
             },
             init::Monotonics(mono),
         )
     }
 
-    #[task(local = [pot_obj], shared = [pwm_obj, lcd], priority = 1)]
-    fn read_pot_and_update_fan(cx: read_pot_and_update_fan::Context) {
-        let new_duty_percent = cx.local.pot_obj.read_percent();
+    #[task(shared = [analog_readings, pwm_obj, display], priority = 1)]
+    fn read_pot_and_update_fan(mut cx: read_pot_and_update_fan::Context) {
+        // Locked on its own and only for a copy, as it holds off the sampler
+        let new_duty_percent = cx
+            .shared
+            .analog_readings
+            .lock(|readings| readings.pot_percent);
 
         // Get current time for any functions that might need it (though not directly used here yet)
         // let current_time = monotonics::AppMono::now();
         // let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
 
-        cx.shared.lock(|shared| {
-            let pwm_obj = &mut shared.pwm_obj;
-            let lcd = &mut shared.lcd;
-
+        (cx.shared.pwm_obj, cx.shared.display).lock(|pwm_obj, display| {
             if new_duty_percent != pwm_obj.get_duty() {
                 pwm_obj.set_duty(new_duty_percent);
-                lcd.write_duty_cycle(new_duty_percent as u8).unwrap();
+                display.draw(|d| show_fan(d, &pwm_obj.telemetry()));
+                display_wake::spawn().ok(); // Already pending is fine
                 // defmt::println!("Fan Duty: {}%", new_duty_percent);
             }
         });
@@ -190,58 +464,617 @@ mod app {
         read_pot_and_update_fan::spawn_after(100.millis()).unwrap();
     }
 
-    #[task(binds = EXTI15_10, local = [user_button], shared = [pwm_obj, lcd, rgb_needs_lcd_update], priority = 3)]
-    fn user_button_handler(cx: user_button_handler::Context) {
+    /// Follow the room light with the LED and display brightness
+    #[task(local = [light_sensor, ambient: AmbientLight = AmbientLight::new(AMBIENT_SMOOTHING_SHIFT), display_level: Option<u8> = None], shared = [analog_readings, pwm_obj, aux_rgb, display], priority = 1)]
+    fn read_ambient_light(mut cx: read_ambient_light::Context) {
+        #[cfg(not(feature = "bh1750"))]
+        let reading = cx.shared.analog_readings.lock(|readings| readings.light);
+        #[cfg(feature = "bh1750")]
+        let reading = cx.local.light_sensor.read_lux().ok(); // Last level kept while missing
+
+        if let Some(reading) = reading {
+            let level = cx.local.ambient.update(reading);
+            let led_level = LED_AMBIENT_CURVE.brightness(level);
+            let display_level = DISPLAY_AMBIENT_CURVE.brightness(level);
+            let display_changed = *cx.local.display_level != Some(display_level);
+            *cx.local.display_level = Some(display_level);
+
+            let shared = (cx.shared.pwm_obj, cx.shared.aux_rgb, cx.shared.display);
+            shared.lock(|pwm_obj, aux_rgb, display| {
+                if let Some(rgb_obj) = &mut pwm_obj.rgb {
+                    rgb_obj.set_ambient_level(led_level);
+                }
+                aux_rgb.set_ambient_level(led_level);
+                if display_changed {
+                    display.set_backlight_level(display_level);
+                }
+            });
+        }
+
+        read_ambient_light::spawn_after(AMBIENT_READ_MS.millis()).unwrap();
+    }
+
+    /// Feed the fan speed and temperature to the lighting and the display
+    #[task(local = [counted_at_ms: Option<u32> = None], shared = [tach_pulses, analog_readings, pwm_obj, display], priority = 1)]
+    fn read_fan_sensors(mut cx: read_fan_sensors::Context) {
         let current_time = monotonics::AppMono::now();
         let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
 
-        let mut button_pressed = false;
-        if let DebouncedOutput::Changed(is_low) = cx.local.user_button.is_low(current_time_ms) {
-            // Pass current time
-            if !is_low {
-                // Assuming !is_low means button was pressed and released (rising edge of signal if active low)
-                button_pressed = true;
+        // Both locked on their own and only for a copy, as they hold off
+        // the interrupts filling them
+        let pulses = cx.shared.tach_pulses.lock(core::mem::take);
+        let temperature = cx
+            .shared
+            .analog_readings
+            .lock(|readings| readings.temperature_deci_c);
+
+        // The first count started at an unknown time, so it is dropped
+        let rpm = cx
+            .local
+            .counted_at_ms
+            .replace(current_time_ms)
+            .filter(|_| FAN_TACH)
+            .map(|counted_at| fan_sensors::rpm(pulses, current_time_ms.wrapping_sub(counted_at)));
+
+        (cx.shared.pwm_obj, cx.shared.display).lock(|pwm_obj, display| {
+            pwm_obj.set_rpm(rpm);
+            pwm_obj.set_temperature(Some(temperature));
+            display.draw(|d| show_fan(d, &pwm_obj.telemetry()));
+        });
+
+        read_fan_sensors::spawn_after(FAN_SENSOR_READ_MS.millis()).unwrap();
+    }
+
+    /// Count a fan tachometer pulse
+    #[task(binds = EXTI0, local = [tach_pin], shared = [tach_pulses], priority = 3)]
+    fn fan_tach_pulse(mut cx: fan_tach_pulse::Context) {
+        cx.local.tach_pin.clear_interrupt_pending_bit();
+        cx.shared
+            .tach_pulses
+            .lock(|pulses| *pulses = pulses.wrapping_add(1));
+    }
+
+    /// Wait for the button to stop bouncing before reading it
+    ///
+    /// Every edge pushes the read back by the debounce delay, so it happens
+    /// once the level has held that long.
+    #[task(binds = EXTI15_10, local = [settle_handle: Option<user_button_settled::SpawnHandle> = None], priority = 3)]
+    fn user_button_edge(cx: user_button_edge::Context) {
+        let delay_ms = BUTTON_DEBOUNCE_MS.millis();
+        let settle_handle = cx.local.settle_handle.take();
+        *cx.local.settle_handle = settle_handle
+            .and_then(|handle| handle.reschedule_after(delay_ms).ok())
+            .or_else(|| user_button_settled::spawn_after(delay_ms).ok());
+
+        // Clear the interrupt pending bit for PC13 (EXTI line 13)
+        unsafe { hal::pac::EXTI::steal().pr.write(|w| w.pr13().set_bit()) };
+    }
+
+    #[task(local = [user_button, pressed_at_ms: Option<u32> = None], shared = [pwm_obj, aux_rgb, lighting_clock, sequencer, rgb_needs_display_update], priority = 2)]
+    fn user_button_settled(cx: user_button_settled::Context) {
+        let current_time = monotonics::AppMono::now();
+        let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
+
+        // Held time of a completed press, measured on release; both ends are
+        // read the same debounce delay late
+        let mut held_ms = None;
+        if let DebouncedOutput::Changed(is_low) = cx.local.user_button.is_low() {
+            if is_low {
+                *cx.local.pressed_at_ms = Some(current_time_ms);
+            } else {
+                held_ms = Some(
+                    cx.local
+                        .pressed_at_ms
+                        .take()
+                        .map_or(0, |pressed_at| current_time_ms.wrapping_sub(pressed_at)),
+                );
             }
         }
 
-        if button_pressed {
+        if let Some(held_ms) = held_ms {
             cx.shared.lock(|shared| {
                 let pwm_obj = &mut shared.pwm_obj;
-                // let lcd = &mut shared.lcd; // Not directly used here for LCD write
-                let rgb_update_flag = &mut shared.rgb_needs_lcd_update;
+                let rgb_update_flag = &mut shared.rgb_needs_display_update;
 
                 if let Some(rgb_obj) = &mut pwm_obj.rgb {
-                    rgb_obj.increment_mode(current_time_ms).unwrap(); // Pass current time
-                    defmt::println!("RGB mode change via button!");
-                    *rgb_update_flag = true; // Signal that LCD needs to update RGB mode text
+                    if held_ms >= POWER_HOLD_MS {
+                        let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+                        let on = !rgb_obj.is_on();
+                        rgb_obj.set_on(on, lighting_ms);
+                        shared.aux_rgb.set_on(on, lighting_ms);
+                        if on {
+                            periodic_rgb_update::spawn().ok(); // Still going if the fade out was not over
+                        }
+                        defmt::println!("RGB on {} via button!", on);
+                        *rgb_update_flag = true;
+                    } else if held_ms >= BRIGHTNESS_HOLD_MS {
+                        let brightness = rgb_obj.step_brightness();
+                        shared.aux_rgb.set_brightness(brightness);
+                        defmt::println!("RGB brightness {} via button!", brightness);
+                    } else {
+                        let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+                        shared.sequencer.stop(); // The user takes over
+                        rgb_obj.end_scenes();
+                        rgb_obj.increment_mode(lighting_ms).unwrap();
+                        defmt::println!("RGB mode change via button!");
+                        *rgb_update_flag = true; // Signal that the display needs to update RGB mode text
+                    }
                 }
             });
+            display_wake::spawn().ok(); // Already pending is fine
         }
-
-        // Clear the interrupt pending bit for PC13 (EXTI line 13)
-        unsafe { hal::pac::EXTI::steal().pr.write(|w| w.pr13().set_bit()) };
     }
 
-    #[task(shared = [pwm_obj, lcd, rgb_needs_lcd_update], priority = 2)]
+    #[task(shared = [pwm_obj, aux_rgb, lighting_clock, config, sequencer, rgb_needs_display_update, audio_levels], priority = 2)]
     fn periodic_rgb_update(cx: periodic_rgb_update::Context) {
         let current_time = monotonics::AppMono::now();
-        let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
 
-        cx.shared.lock(|shared| {
+        let is_dark = cx.shared.lock(|shared| {
             let pwm_obj = &mut shared.pwm_obj;
-            let lcd = &mut shared.lcd;
-            let rgb_update_flag = &mut shared.rgb_needs_lcd_update;
+            let rgb_update_flag = &mut shared.rgb_needs_display_update;
+            let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
 
             if let Some(rgb_obj) = &mut pwm_obj.rgb {
+                // Host frames take precedence; the playlist goes on after them
+                // and holds still while the LEDs are off
+                if !rgb_obj.is_direct_mode() && rgb_obj.is_on() {
+                    if let Some(scene) = shared.sequencer.tick(lighting_ms, &shared.config.scenes) {
+                        rgb_obj.apply_scene(&scene, lighting_ms).unwrap();
+                        *rgb_update_flag = true;
+                    }
+                }
+                // Stopped by a command or out of scenes
+                if shared.sequencer.playing().is_none() {
+                    rgb_obj.end_scenes();
+                }
                 if *rgb_update_flag {
-                    lcd.write_message(rgb_obj.get_mode_text(), (1, 0)).unwrap();
-                    *rgb_update_flag = false; // Reset flag
+                    // Drawn at the display's priority; tried again next frame
+                    // if the previous name is still pending
+                    *rgb_update_flag = show_mode_text::spawn(rgb_obj.get_mode_text()).is_err();
                 }
-                rgb_obj.update(current_time_ms).unwrap(); // Pass current time
+                rgb_obj.set_audio_levels(*shared.audio_levels);
+                rgb_obj.update(lighting_ms).unwrap();
             }
+
+            shared.aux_rgb.set_fan_telemetry(pwm_obj.telemetry());
+            shared.aux_rgb.set_audio_levels(*shared.audio_levels);
+            shared.aux_rgb.update(lighting_ms).unwrap();
+
+            pwm_obj.rgb.as_ref().is_none_or(|rgb_obj| rgb_obj.is_dark()) && shared.aux_rgb.is_dark()
         });
 
-        periodic_rgb_update::spawn_after(50.millis()).unwrap(); // Adjust interval as needed
+        // Frames stop once the LEDs have faded out; turning them on spawns
+        // this again
+        if !is_dark {
+            periodic_rgb_update::spawn_after(RGB_UPDATE_MS.millis()).ok(); // Already spawned by the button
+        }
+    }
+
+    /// Take one microphone sample, handing on every full block
+    ///
+    /// Above every lock ceiling and owning the ADC, so nothing holds a
+    /// sample off; the pot and LDR are read here too, after a sample.
+    #[task(binds = TIM5, local = [pot_obj, mic, ldr, mic_timer, block: SampleBlock = [0; audio::BLOCK_LEN], sample_qty: usize = 0, slow_countdown: u32 = 0], shared = [analog_readings], priority = 5)]
+    fn sample_mic(mut cx: sample_mic::Context) {
+        cx.local.mic_timer.clear_flags(Flag::Update);
+
+        let pot_obj = cx.local.pot_obj;
+        cx.local.block[*cx.local.sample_qty] = pot_obj.sample(&*cx.local.mic);
+        *cx.local.sample_qty += 1;
+
+        if *cx.local.sample_qty == audio::BLOCK_LEN {
+            *cx.local.sample_qty = 0;
+            analyze_audio::spawn(*cx.local.block).ok(); // Previous one still pending, drop this one
+        }
+
+        // The pot and temperature conversions are slow ones, but still done
+        // long before the next sample is due
+        if *cx.local.slow_countdown == 0 {
+            *cx.local.slow_countdown = SLOW_INPUT_SAMPLES;
+            let readings = AnalogReadings {
+                pot_percent: pot_obj.read_percent(),
+                light: cx.local.ldr.as_ref().map(|ldr| pot_obj.sample(ldr)),
+                temperature_deci_c: pot_obj.read_temperature_deci_c(),
+            };
+            cx.shared.analog_readings.lock(|shared| *shared = readings);
+        }
+        *cx.local.slow_countdown -= 1;
+    }
+
+    #[task(local = [analyzer: Analyzer = Analyzer::new()], shared = [audio_levels], priority = 1)]
+    fn analyze_audio(cx: analyze_audio::Context, block: SampleBlock) {
+        let levels = cx.local.analyzer.analyze(&block);
+
+        cx.shared.lock(|shared| *shared.audio_levels = levels);
+    }
+
+    /// Scroll a new RGB mode name in from its start
+    #[task(shared = [display, mode_marquee], priority = 1)]
+    fn show_mode_text(cx: show_mode_text::Context, text: &'static str) {
+        let current_time = monotonics::AppMono::now();
+        let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
+
+        cx.shared.lock(|shared| {
+            let display = &mut shared.display;
+            let mode_marquee = &mut shared.mode_marquee;
+
+            mode_marquee.set_text(text, current_time_ms);
+            display.draw(|d| show_marquee(d, mode_marquee));
+        });
+    }
+
+    #[task(shared = [display, mode_marquee], priority = 1)]
+    fn display_marquee_update(cx: display_marquee_update::Context) {
+        let current_time = monotonics::AppMono::now();
+        let current_time_ms = current_time.duration_since_epoch().to_millis() as u32;
+
+        cx.shared.lock(|shared| {
+            let display = &mut shared.display;
+            let mode_marquee = &mut shared.mode_marquee;
+
+            if mode_marquee.advance(current_time_ms) {
+                display.draw(|d| show_marquee(d, mode_marquee));
+            }
+        });
+
+        display_marquee_update::spawn_after(50.millis()).unwrap();
+    }
+
+    #[task(shared = [pwm_obj, display, duty_history], priority = 1)]
+    fn sample_duty_history(cx: sample_duty_history::Context) {
+        cx.shared.lock(|shared| {
+            let display = &mut shared.display;
+            let duty_history = &mut shared.duty_history;
+
+            duty_history.push(shared.pwm_obj.get_duty());
+            display.draw(|d| show_history(d, duty_history));
+        });
+
+        sample_duty_history::spawn_after(1.secs()).unwrap();
+    }
+
+    /// Turn the display backlight on and restart its inactivity timeout
+    ///
+    /// Spawn this on any user input or alarm that should wake the display.
+    #[task(local = [off_handle: Option<display_backlight_off::SpawnHandle> = None], shared = [display], priority = 1)]
+    fn display_wake(cx: display_wake::Context) {
+        let timeout_secs = cx.shared.lock(|shared| {
+            let display = &mut shared.display;
+
+            if !display.is_backlight_on() {
+                display.set_backlight(true);
+            }
+            display.device().backlight_timeout()
+        });
+
+        // Push the pending off event back, or arm a new one if it already fired
+        let off_handle = cx.local.off_handle.take();
+        match timeout_secs {
+            Some(secs) => {
+                *cx.local.off_handle = off_handle
+                    .and_then(|handle| handle.reschedule_after(secs.secs()).ok())
+                    .or_else(|| display_backlight_off::spawn_after(secs.secs()).ok());
+            }
+            None => {
+                if let Some(handle) = off_handle {
+                    handle.cancel().ok();
+                }
+            }
+        }
+    }
+
+    #[task(shared = [display], priority = 1)]
+    fn display_backlight_off(cx: display_backlight_off::Context) {
+        cx.shared.lock(|shared| {
+            shared.display.set_backlight(false);
+        });
+    }
+
+    /// Bring an offline display back and redraw everything on it
+    ///
+    /// Recovery busy-waits for milliseconds, so what gets redrawn is copied
+    /// out first and only the display stays locked for it. No task above
+    /// priority 1 shares the display, so frames and the button go on.
+    #[task(shared = [pwm_obj, display, mode_marquee, duty_history], priority = 1)]
+    fn display_recovery(mut cx: display_recovery::Context) {
+        if !cx.shared.display.lock(|display| display.is_online()) {
+            let fan = cx.shared.pwm_obj.lock(|pwm_obj| pwm_obj.telemetry());
+            let mode_marquee = cx.shared.mode_marquee.lock(|mode_marquee| *mode_marquee);
+            let duty_history = cx.shared.duty_history.lock(|duty_history| *duty_history);
+
+            cx.shared.display.lock(|display| {
+                if !i2c_recovery::recover_i2c1() {
+                    defmt::warn!("I2C1 SDA still held low");
+                }
+
+                if display.try_recover() {
+                    display.draw(|d| {
+                        show_fan(d, &fan)?;
+                        show_marquee(d, &mode_marquee)?;
+                        show_history(d, &duty_history)
+                    });
+                }
+            });
+        }
+
+        display_recovery::spawn_after(DISPLAY_RETRY_SECS.secs()).unwrap();
+    }
+
+    #[task(binds = USART2, local = [host_rx, parser: adalight::Parser = adalight::Parser::new(), lines: commands::LineReader = commands::LineReader::new()], priority = 4)]
+    fn host_serial_rx(cx: host_serial_rx::Context) {
+        // Frames are handed on by value, so no lighting lock is taken here
+        // and bytes are never lost to a long render
+        loop {
+            match cx.local.host_rx.read() {
+                Ok(byte) => {
+                    // Command lines may only start between Adalight frames
+                    if cx.local.lines.is_active() {
+                        if let Some(line) = cx.local.lines.feed(byte) {
+                            run_command::spawn(line).ok(); // Previous one still running, drop this one
+                        }
+                    } else if byte == commands::PREFIX && cx.local.parser.is_idle() {
+                        cx.local.lines.start();
+                    } else if let Some(frame) = cx.local.parser.feed(byte) {
+                        show_host_frame::spawn(*frame).ok(); // Previous one still pending, drop this one
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                // Overrun or framing error; resync on the next header
+                Err(nb::Error::Other(_)) => cx.local.parser.reset(),
+            }
+        }
+    }
+
+    #[task(local = [timeout_handle: Option<host_timeout::SpawnHandle> = None], shared = [pwm_obj, lighting_clock, rgb_needs_display_update], priority = 2)]
+    fn show_host_frame(cx: show_host_frame::Context, frame: adalight::HostFrame) {
+        let current_time = monotonics::AppMono::now();
+
+        cx.shared.lock(|shared| {
+            let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+
+            if let Some(rgb_obj) = &mut shared.pwm_obj.rgb {
+                if !rgb_obj.is_direct_mode() {
+                    rgb_obj.set_direct_mode(lighting_ms).ok();
+                    *shared.rgb_needs_display_update = true;
+                }
+                rgb_obj.set_direct_leds(0, frame.leds());
+            }
+        });
+
+        // Push the fallback back, or arm a new one if it already fired
+        let timeout_handle = cx.local.timeout_handle.take();
+        *cx.local.timeout_handle = timeout_handle
+            .and_then(|handle| handle.reschedule_after(ADALIGHT_TIMEOUT_MS.millis()).ok())
+            .or_else(|| host_timeout::spawn_after(ADALIGHT_TIMEOUT_MS.millis()).ok());
+    }
+
+    #[task(shared = [pwm_obj, lighting_clock, rgb_needs_display_update], priority = 2)]
+    fn host_timeout(cx: host_timeout::Context) {
+        let current_time = monotonics::AppMono::now();
+
+        cx.shared.lock(|shared| {
+            let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+
+            if let Some(rgb_obj) = &mut shared.pwm_obj.rgb {
+                rgb_obj.leave_direct_mode(lighting_ms).ok();
+                *shared.rgb_needs_display_update = true;
+                defmt::println!("Host went quiet, back to the effect");
+            }
+        });
+    }
+
+    /// Carry out a command line from the host and reply "ok" or "error: ..."
+    #[task(local = [host_tx, config_store], shared = [pwm_obj, aux_rgb, lighting_clock, config, sequencer, rgb_needs_display_update], priority = 1)]
+    fn run_command(mut cx: run_command::Context, line: commands::Line) {
+        let current_time = monotonics::AppMono::now();
+        let host_tx = cx.local.host_tx;
+        let command = match line.as_str().map(commands::parse) {
+            Some(Ok(command)) => command,
+            Some(Err(message)) => {
+                writeln!(host_tx, "error: {}", message).ok();
+                return;
+            }
+            None => {
+                writeln!(host_tx, "error: not text").ok();
+                return;
+            }
+        };
+
+        // Replies and flash writes are slow, so they are done on a copy
+        // rather than with the lighting locked
+        let result = match command {
+            Command::SceneList => {
+                let config = cx.shared.config.lock(|config| *config);
+                for (index, scene) in config.scenes.as_slice().iter().enumerate() {
+                    let name = effects::EFFECTS
+                        .get(usize::from(scene.mode))
+                        .map_or("?", |effect| effect.name());
+                    let color = scene.color;
+                    writeln!(
+                        host_tx,
+                        "{}: {} {}ms speed {} color {:02x}{:02x}{:02x} {} {}ms",
+                        index,
+                        name,
+                        scene.duration_ms,
+                        scene.speed,
+                        color.r,
+                        color.g,
+                        color.b,
+                        scene.transition.kind.name(),
+                        scene.transition.duration_ms,
+                    )
+                    .ok();
+                }
+                Ok(())
+            }
+            Command::SceneAdd(scene) => cx
+                .shared
+                .config
+                .lock(|config| config.scenes.push(scene))
+                .then_some(())
+                .ok_or("scene list full"),
+            Command::SceneSet(index, scene) => cx
+                .shared
+                .config
+                .lock(|config| config.scenes.set(index, scene))
+                .then_some(())
+                .ok_or("no such scene"),
+            Command::SceneDelete(index) => cx
+                .shared
+                .config
+                .lock(|config| config.scenes.remove(index))
+                .map(|_| ())
+                .ok_or("no such scene"),
+            Command::SceneClear => {
+                cx.shared.config.lock(|config| config.scenes.clear());
+                Ok(())
+            }
+            Command::Play(playlist) => {
+                cx.shared
+                    .sequencer
+                    .lock(|sequencer| sequencer.start(playlist));
+                Ok(())
+            }
+            Command::Stop => {
+                cx.shared.sequencer.lock(|sequencer| sequencer.stop());
+                Ok(())
+            }
+            Command::ZoneList => {
+                let mut zones = [None; MAX_ZONES];
+                cx.shared.lock(|shared| {
+                    if let Some(rgb_obj) = &shared.pwm_obj.rgb {
+                        for ((slot, zone), index) in zones.iter_mut().zip(rgb_obj.zones()).zip(0..)
+                        {
+                            *slot = rgb_obj.zone_mode(index).map(|mode| (*zone, mode));
+                        }
+                    }
+                });
+                for (number, (zone, mode)) in (1..).zip(zones.iter().flatten()) {
+                    let name = effects::EFFECTS
+                        .get(usize::from(*mode))
+                        .map_or("?", |effect| effect.name());
+                    writeln!(
+                        host_tx,
+                        "{}: {} LEDs {}-{}{} {}",
+                        number,
+                        zone.name,
+                        zone.start,
+                        zone.start + zone.len - 1,
+                        if zone.reversed { " reversed" } else { "" },
+                        name,
+                    )
+                    .ok();
+                }
+                Ok(())
+            }
+            Command::Zone(zone, setting) => cx.shared.lock(|shared| {
+                let Some(rgb_obj) = &mut shared.pwm_obj.rgb else {
+                    return Err("no fan strip");
+                };
+                if rgb_obj.zone_mode(zone).is_none() {
+                    return Err("no such zone");
+                }
+
+                match setting {
+                    ZoneSetting::Mode(mode) => {
+                        let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+                        rgb_obj
+                            .set_zone_mode(zone, mode, lighting_ms)
+                            .map_err(|_| "strip error")?;
+                        *shared.rgb_needs_display_update = true;
+                    }
+                    ZoneSetting::Brightness(brightness) => {
+                        rgb_obj.set_zone_brightness(zone, brightness)
+                    }
+                    ZoneSetting::Reversed(reversed) => rgb_obj.set_zone_reversed(zone, reversed),
+                }
+                Ok(())
+            }),
+            Command::Strip(strip, setting) => cx.shared.lock(|shared| {
+                match strip {
+                    StripId::Fan => {
+                        let rgb_obj = shared.pwm_obj.rgb.as_mut().ok_or("no fan strip")?;
+                        shared.config.fan_correction = Some(change_correction(rgb_obj, setting));
+                    }
+                    StripId::Aux => {
+                        shared.config.aux_correction =
+                            Some(change_correction(shared.aux_rgb, setting));
+                    }
+                }
+                Ok(())
+            }),
+            // Both strips show the same user colors
+            Command::Color(slot, color) => cx.shared.lock(|shared| {
+                let user = &mut shared.config.user;
+                user.set_color(slot, color);
+                if let Some(rgb_obj) = &mut shared.pwm_obj.rgb {
+                    rgb_obj.set_user_colors(*user);
+                }
+                shared.aux_rgb.set_user_colors(*user);
+                Ok(())
+            }),
+            Command::Palette(slot, colors) => cx.shared.lock(|shared| {
+                let user = &mut shared.config.user;
+                user.set_palette(slot, colors.as_slice());
+                if let Some(rgb_obj) = &mut shared.pwm_obj.rgb {
+                    rgb_obj.set_user_colors(*user);
+                }
+                shared.aux_rgb.set_user_colors(*user);
+                Ok(())
+            }),
+            Command::Speed(speed) => cx.shared.pwm_obj.lock(|pwm_obj| {
+                let rgb_obj = pwm_obj.rgb.as_mut().ok_or("no fan strip")?;
+                rgb_obj.set_speed(speed);
+                Ok(())
+            }),
+            Command::Direct(fill) => cx.shared.lock(|shared| {
+                let rgb_obj = shared.pwm_obj.rgb.as_mut().ok_or("no fan strip")?;
+                let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+
+                // Stays until the mode is changed, as host frames are not
+                // coming to time it out
+                rgb_obj
+                    .set_direct_mode(lighting_ms)
+                    .map_err(|_| "strip error")?;
+                match fill {
+                    DirectFill::Solid(color) => rgb_obj.fill_direct(color),
+                    DirectFill::Gradient(from, to) => rgb_obj.fill_direct_gradient(from, to),
+                }
+                *shared.rgb_needs_display_update = true;
+                Ok(())
+            }),
+            Command::Light(on) => {
+                cx.shared.lock(|shared| {
+                    let lighting_ms = shared.lighting_clock.now_ms(current_time.ticks());
+                    if let Some(rgb_obj) = &mut shared.pwm_obj.rgb {
+                        rgb_obj.set_on(on, lighting_ms);
+                    }
+                    shared.aux_rgb.set_on(on, lighting_ms);
+                    *shared.rgb_needs_display_update = true;
+                });
+                if on {
+                    periodic_rgb_update::spawn().ok(); // Still going if the fade out was not over
+                }
+                Ok(())
+            }
+            Command::Save => {
+                // Whatever plays now comes back on power up
+                let config = cx.shared.lock(|shared| Config {
+                    autoplay: shared.sequencer.playing(),
+                    ..*shared.config
+                });
+                cx.local
+                    .config_store
+                    .save(config)
+                    .map_err(|_| "flash write failed")
+            }
+        };
+
+        match result {
+            Ok(()) => writeln!(host_tx, "ok"),
+            Err(message) => writeln!(host_tx, "error: {}", message),
+        }
+        .ok();
     }
 
     // Optional: Idle task
@@ -0,0 +1,172 @@
+use embedded_graphics::{
+    mono_font::{
+        MonoFont, MonoTextStyleBuilder,
+        ascii::{FONT_6X10, FONT_10X20},
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use ssd1306::{I2CDisplayInterface, Ssd1306, mode::BufferedGraphicsMode, prelude::*};
+use stm32f4xx_hal::i2c::{self, I2c};
+
+use crate::display::{self, History};
+
+const OLED_WIDTH: i32 = 128;
+const OLED_HEIGHT: i32 = 64;
+const CHAR_WIDTH: i32 = 6; // FONT_6X10 cell, 21 columns by 6 rows
+const ROW_HEIGHT: i32 = 10;
+
+/// 128x64 SSD1306 OLED on I2C, drawn through a frame buffer
+///
+/// Drawing only touches the buffer; `flush` sends the changed area.
+pub struct I2COled<I2C>
+where
+    I2C: i2c::Instance,
+{
+    device:
+        Ssd1306<I2CInterface<I2c<I2C>>, DisplaySize128x64, BufferedGraphicsMode<DisplaySize128x64>>,
+    display_on: bool,
+    backlight_timeout_secs: u32, // 0 keeps the panel on
+}
+
+impl<I2C> I2COled<I2C>
+where
+    I2C: i2c::Instance,
+{
+    /// Wrap the bus without touching it; `reinit` brings the panel up
+    pub fn new(i2c_bus: I2c<I2C>) -> Self {
+        let interface = I2CDisplayInterface::new(i2c_bus);
+        let device = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+            .into_buffered_graphics_mode();
+
+        Self {
+            device,
+            display_on: true,
+            backlight_timeout_secs: 0,
+        }
+    }
+
+    fn draw_text(
+        &mut self,
+        position: (u8, u8),
+        text: &str,
+        font: &MonoFont,
+    ) -> Result<(), crate::error::Error> {
+        // Background is drawn too, so old text under the new one is cleared
+        let style = MonoTextStyleBuilder::new()
+            .font(font)
+            .text_color(BinaryColor::On)
+            .background_color(BinaryColor::Off)
+            .build();
+        let top_left = Point::new(
+            i32::from(position.1) * CHAR_WIDTH,
+            i32::from(position.0) * ROW_HEIGHT,
+        );
+
+        Text::with_baseline(text, top_left, style, Baseline::Top)
+            .draw(&mut self.device)
+            .map_err(|_| crate::error::Error::I2C)?;
+
+        Ok(())
+    }
+}
+
+impl<I2C> display::Display for I2COled<I2C>
+where
+    I2C: i2c::Instance,
+{
+    const ROWS: u8 = (OLED_HEIGHT / ROW_HEIGHT) as u8;
+    const COLUMNS: usize = (OLED_WIDTH / CHAR_WIDTH) as usize;
+
+    fn write_at(&mut self, position: (u8, u8), text: &str) -> Result<(), crate::error::Error> {
+        self.draw_text(position, text, &FONT_6X10)
+    }
+
+    fn write_large(&mut self, position: (u8, u8), text: &str) -> Result<(), crate::error::Error> {
+        self.draw_text(position, text, &FONT_10X20)
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), crate::error::Error> {
+        self.device
+            .set_display_on(on)
+            .map_err(|_| crate::error::Error::I2C)?;
+        self.display_on = on;
+
+        Ok(())
+    }
+
+    fn set_backlight_level(&mut self, level: u8) -> Result<(), crate::error::Error> {
+        // Contrast sets the pixel current, which is all an OLED has to dim
+        self.device
+            .set_brightness(Brightness::custom(2, level))
+            .map_err(|_| crate::error::Error::I2C)
+    }
+
+    fn reinit(&mut self) -> Result<(), crate::error::Error> {
+        self.device.init().map_err(|_| crate::error::Error::I2C)?;
+        self.device
+            .set_display_on(self.display_on)
+            .map_err(|_| crate::error::Error::I2C)?;
+
+        // Panel RAM is unknown now, so push a blank frame and let the caller redraw
+        self.device.clear_buffer();
+        self.device.flush().map_err(|_| crate::error::Error::I2C)
+    }
+
+    fn set_backlight_timeout(&mut self, secs: u32) {
+        self.backlight_timeout_secs = secs;
+    }
+
+    fn backlight_timeout(&self) -> Option<u32> {
+        match self.backlight_timeout_secs {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+
+    fn draw_history<const N: usize>(
+        &mut self,
+        row: u8,
+        history: &History<N>,
+    ) -> Result<(), crate::error::Error> {
+        let top = i32::from(row) * ROW_HEIGHT;
+        let height = (OLED_HEIGHT - top).max(1);
+        let area = Rectangle::new(
+            Point::new(0, top),
+            Size::new(OLED_WIDTH as u32, height as u32),
+        );
+        area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+            .draw(&mut self.device)
+            .map_err(|_| crate::error::Error::I2C)?;
+
+        // Newest sample at the right edge, scaled so the peak fills the area
+        let peak = i32::from(history.max().max(1));
+        let step = (OLED_WIDTH / N.max(1) as i32).max(1);
+        let first_x = OLED_WIDTH - 1 - (history.len() as i32 - 1) * step;
+        let to_point = |i: usize, sample: u16| {
+            let y = OLED_HEIGHT - 1 - i32::from(sample) * (height - 1) / peak;
+            Point::new(first_x + i as i32 * step, y)
+        };
+
+        let line_style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        let mut last: Option<Point> = None;
+        for (i, sample) in history.iter().enumerate() {
+            let point = to_point(i, sample);
+            if let Some(prev) = last {
+                Line::new(prev, point)
+                    .into_styled(line_style)
+                    .draw(&mut self.device)
+                    .map_err(|_| crate::error::Error::I2C)?;
+            }
+            last = Some(point);
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), crate::error::Error> {
+        self.device.flush().map_err(|_| crate::error::Error::I2C)
+    }
+}
@@ -0,0 +1,139 @@
+use smart_leds::RGB8;
+
+use crate::effects::scale_color;
+
+/// Current the LEDs may draw and what each of them draws
+#[derive(Clone, Copy)]
+pub struct PowerBudget {
+    /// Total for all LEDs, 0 for no limit
+    pub max_milliamps: u32,
+    /// Red, green and blue current of one LED at full value
+    pub channel_microamps: [u32; 3],
+    /// Current of one LED with all channels off
+    pub idle_microamps: u32,
+}
+
+impl Default for PowerBudget {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl PowerBudget {
+    /// 400mA, leaving the board some of what a USB 2.0 port supplies
+    pub const DEFAULT: Self = Self::new(400);
+
+    /// Budget of `max_milliamps` for WS2812B LEDs: about 20mA per channel,
+    /// 1mA quiescent
+    pub const fn new(max_milliamps: u32) -> Self {
+        Self {
+            max_milliamps,
+            channel_microamps: [20_000; 3],
+            idle_microamps: 1_000,
+        }
+    }
+
+    /// Current drawn by the lit channels, without the idle current
+    fn lit_microamps(&self, leds: &[RGB8]) -> u32 {
+        let [r, g, b] = self.channel_microamps;
+        leds.iter()
+            .map(|led| (u32::from(led.r) * r + u32::from(led.g) * g + u32::from(led.b) * b) / 255)
+            .sum()
+    }
+
+    /// Estimated current of showing `leds`, as sent to the strip
+    pub fn estimate_milliamps(&self, leds: &[RGB8]) -> u32 {
+        let idle = self.idle_microamps * leds.len() as u32;
+        (idle + self.lit_microamps(leds)).div_ceil(1000)
+    }
+
+    /// Dim `leds` just enough to stay within `max_milliamps`
+    ///
+    /// All LEDs are scaled by the same amount so colors keep their balance.
+    /// Returns the scale applied, 255 when the frame already fits.
+    pub fn limit(&self, leds: &mut [RGB8]) -> u8 {
+        if self.max_milliamps == 0 {
+            return 255;
+        }
+
+        let idle = self.idle_microamps * leds.len() as u32;
+        let available = (self.max_milliamps * 1000).saturating_sub(idle);
+        let lit = self.lit_microamps(leds);
+        if lit <= available {
+            return 255;
+        }
+
+        // `scale8` multiplies by (scale + 1) / 256 rounding down, so this
+        // never ends up above the budget
+        let scale = (u64::from(available) * 256 / u64::from(lit)).saturating_sub(1) as u8;
+        for led in leds.iter_mut() {
+            *led = scale_color(*led, scale);
+        }
+
+        scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: RGB8 = RGB8::new(255, 255, 255);
+
+    #[test]
+    fn estimate_counts_channels_and_idle() {
+        let budget = PowerBudget::default();
+
+        assert_eq!(budget.estimate_milliamps(&[RGB8::default(); 8]), 8);
+        assert_eq!(budget.estimate_milliamps(&[WHITE; 8]), 8 * 61);
+        assert_eq!(budget.estimate_milliamps(&[RGB8::new(255, 0, 0)]), 21);
+        assert_eq!(budget.estimate_milliamps(&[RGB8::new(128, 0, 0)]), 12); // Rounded up
+    }
+
+    #[test]
+    fn frame_within_budget_is_untouched() {
+        let budget = PowerBudget::default();
+        let mut leds = [RGB8::new(255, 0, 0); 8];
+
+        assert_eq!(budget.limit(&mut leds), 255);
+        assert_eq!(leds, [RGB8::new(255, 0, 0); 8]);
+    }
+
+    #[test]
+    fn frame_over_budget_is_scaled_below_it() {
+        let budget = PowerBudget::default();
+        let mut leds = [WHITE; 12];
+
+        let scale = budget.limit(&mut leds);
+        assert!(scale < 255);
+        let estimate = budget.estimate_milliamps(&leds);
+        assert!(estimate <= budget.max_milliamps, "{estimate}mA");
+        assert!(estimate > budget.max_milliamps * 9 / 10, "{estimate}mA");
+        // Same scale everywhere keeps the color
+        assert!(leds.iter().all(|led| led.r == led.g && led.g == led.b));
+    }
+
+    #[test]
+    fn zero_budget_means_no_limit() {
+        let budget = PowerBudget {
+            max_milliamps: 0,
+            ..PowerBudget::default()
+        };
+        let mut leds = [WHITE; 64];
+
+        assert_eq!(budget.limit(&mut leds), 255);
+        assert_eq!(leds, [WHITE; 64]);
+    }
+
+    #[test]
+    fn idle_current_alone_over_budget_turns_leds_off() {
+        let budget = PowerBudget {
+            max_milliamps: 5,
+            ..PowerBudget::default()
+        };
+        let mut leds = [WHITE; 8];
+
+        assert_eq!(budget.limit(&mut leds), 0);
+        assert!(leds.iter().all(|led| led.r <= 1));
+    }
+}
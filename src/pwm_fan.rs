@@ -1,39 +1,265 @@
 use core::u16;
 
-use smart_leds::{
-    RGB, RGB8, SmartLedsWrite, colors, gamma,
-    hsv::{Hsv, hsv2rgb},
-};
+use smart_leds::RGB8;
 
-use stm32f4xx_hal::{hal::spi, prelude::*, rcc, timer};
+use stm32f4xx_hal::{prelude::*, rcc, timer};
 
 use defmt;
-use ws2812_spi as ws2812;
 
-pub struct AdjustablePwmFan<SPI, TIM, PINS>
+use crate::correction::ColorCorrection;
+use crate::dither::TemporalDither;
+use crate::sequencer::Scene;
+use crate::strip::{self, MAX_LEDS, Protocol, StripConfig, StripOutput};
+use crate::zone::{MAX_ZONES, Zone};
+
+use crate::effects::{
+    self, EffectParams, Frame, MAX_EFFECTS, NORMAL_SPEED, Rng, clock::ScaledTime,
+    custom::UserColors, direct, reactive::FanTelemetry, sound::AudioLevels, transition::Transition,
+};
+
+pub const BRIGHTNESS_STEPS: [u8; 5] = [16, 48, 96, 160, 255]; // Button cycle
+const POWER_FADE_MS: u64 = 600; // Turning the LEDs on or off
+/// Period effects are drawn at; frames in between only redo the brightness
+/// scaling and dithering of the last drawn one
+const RENDER_MS: u64 = 20;
+
+pub struct AdjustablePwmFan<OUT, TIM, PINS>
 where
-    SPI: spi::SpiBus<u8>,
+    OUT: StripOutput,
     TIM: timer::PwmExt,
     PINS: timer::Pins<TIM>,
 {
     device: timer::PwmHz<TIM, PINS>,
     channel: timer::Channel,
     current_duty: u16,
-    pub rgb: Option<PwmFanRgb<SPI>>,
+    telemetry: FanTelemetry,
+    pub rgb: Option<PwmFanRgb<OUT>>,
 }
 
-pub struct PwmFanRgb<SPI>
+pub struct PwmFanRgb<OUT>
 where
-    SPI: spi::SpiBus<u8>,
+    OUT: StripOutput,
 {
-    pub device: ws2812::Ws2812<SPI>,
-    color_mode: u8,
+    pub device: OUT,
+    config: StripConfig,
+    params: EffectParams,
     brightness: u8,
+    ambient: u8, // Scales the brightness, following the room light
+    power: PowerFade,
+    dither: TemporalDither,
+    speeds: [u8; MAX_EFFECTS], // Per effect, by index in the registry
+    zones: [ZoneLighting; MAX_ZONES],
+    zone_qty: usize,
+    transition: Transition,
+    rng: Rng,
+    corrected: [RGB8; MAX_LEDS], // Last drawn frame, in strip order, before brightness
+    rendered_at_ms: Option<u64>, // None to draw on the next update
+    direct: [RGB8; MAX_LEDS],    // Colors shown in direct mode, in strip order
+    before_scenes: Option<SceneBackup>, // Some while a playlist is applying scenes
+}
+
+/// Settings scenes override, kept to go back to once the playlist stops
+#[derive(Clone, Copy)]
+struct SceneBackup {
+    transition: Transition,
+    color: RGB8,
+    speeds: [u8; MAX_EFFECTS],
+}
+
+/// On/off state of a strip, faded between over `POWER_FADE_MS`
+///
+/// Once faded out, one black frame is sent and the strip is left alone.
+struct PowerFade {
+    on: bool,
+    from_level: u8, // Level the current fade started at
+    start_ms: Option<u64>,
+    dark: bool, // Off, with the black frame sent
+}
+
+impl PowerFade {
+    const fn new() -> Self {
+        Self {
+            on: true,
+            from_level: 255,
+            start_ms: None,
+            dark: false,
+        }
+    }
+
+    fn set_on(&mut self, on: bool, current_time_ms: u64) {
+        if on != self.on {
+            // A fade turned around midway carries on from where it got to
+            self.from_level = self.level(current_time_ms);
+            self.start_ms = Some(current_time_ms);
+            self.on = on;
+            self.dark = false;
+        }
+    }
+
+    /// Brightness scale at `current_time_ms`, 255 when fully on
+    fn level(&self, current_time_ms: u64) -> u8 {
+        let target: u8 = if self.on { 255 } else { 0 };
+        let elapsed_ms = self
+            .start_ms
+            .map_or(POWER_FADE_MS, |start| current_time_ms.saturating_sub(start));
+        if elapsed_ms >= POWER_FADE_MS {
+            return target;
+        }
+
+        let step = (i64::from(target) - i64::from(self.from_level)) * elapsed_ms as i64;
+        (i64::from(self.from_level) + step / POWER_FADE_MS as i64) as u8
+    }
+}
+
+/// Effect and brightness of one zone
+///
+/// Mode changes without a zone number go to every zone.
+struct ZoneLighting {
+    zone: Zone,
+    brightness: u8, // Relative to the strip's
+    current: EffectLayer,
+    previous: EffectLayer, // Effect being transitioned away from
+    transition_start_ms: Option<u64>,
+    mode_before_direct: Option<u8>,
+}
+
+/// One effect with the buffers it renders into
+struct EffectLayer {
+    color_mode: u8,
+    led_qty: usize,
+    leds: [RGB8; MAX_LEDS], // Last rendered frame, before gamma
+    state: [u8; MAX_LEDS],  // Per-LED effect state
+    phase: u32,
+    scaled_time: ScaledTime,
+    last_time_ms: Option<u64>,
+}
+
+impl EffectLayer {
+    fn new(color_mode: u8, led_qty: usize) -> Self {
+        Self {
+            color_mode,
+            led_qty: led_qty.min(MAX_LEDS),
+            leds: [RGB8::default(); MAX_LEDS],
+            state: [0u8; MAX_LEDS],
+            phase: 0,
+            scaled_time: ScaledTime::default(),
+            last_time_ms: None,
+        }
+    }
+
+    fn render(&mut self, current_time_ms: u64, speed: u8, params: &EffectParams, rng: &mut Rng) {
+        // The clock never goes back, but a stale timestamp must not either
+        let elapsed_ms = self.last_time_ms.map_or(0, |last| {
+            u32::try_from(current_time_ms.saturating_sub(last)).unwrap_or(u32::MAX)
+        });
+        self.last_time_ms = Some(current_time_ms);
+        let scaled_elapsed_ms = self.scaled_time.advance(elapsed_ms, speed);
+
+        // Unknown modes leave the LEDs off
+        match effects::EFFECTS.get(usize::from(self.color_mode)) {
+            Some(effect) => effect.render(&mut Frame {
+                leds: &mut self.leds[..self.led_qty],
+                state: &mut self.state[..self.led_qty],
+                time_ms: current_time_ms,
+                elapsed_ms,
+                speed,
+                scaled_ms: self.scaled_time.ms(),
+                scaled_elapsed_ms,
+                phase: &mut self.phase,
+                params,
+                rng,
+            }),
+            None => self.leds = [RGB8::default(); MAX_LEDS],
+        }
+    }
+}
+
+impl ZoneLighting {
+    fn new(zone: Zone, color_mode: u8) -> Self {
+        Self {
+            zone,
+            brightness: 255,
+            current: EffectLayer::new(color_mode, zone.len),
+            previous: EffectLayer::new(color_mode, zone.len),
+            transition_start_ms: None,
+            mode_before_direct: None,
+        }
+    }
+
+    fn is_direct(&self) -> bool {
+        effects::EFFECTS
+            .get(usize::from(self.current.color_mode))
+            .is_some_and(|effect| effect.is_direct())
+    }
+
+    fn set_mode(&mut self, color_mode: u8, current_time_ms: u64) {
+        // The outgoing effect keeps animating until the transition is over
+        self.previous = core::mem::replace(
+            &mut self.current,
+            EffectLayer::new(color_mode, self.zone.len),
+        );
+        self.transition_start_ms = Some(current_time_ms);
+    }
+
+    /// Render the zone's LEDs, in the order its effect draws them
+    fn render(
+        &mut self,
+        current_time_ms: u64,
+        speeds: &[u8; MAX_EFFECTS],
+        transition: &Transition,
+        params: &EffectParams,
+        rng: &mut Rng,
+    ) -> [RGB8; MAX_LEDS] {
+        let speed = speed_of(speeds, self.current.color_mode);
+        self.current.render(current_time_ms, speed, params, rng);
+        let led_qty = self.current.led_qty;
+        let mut leds = self.current.leds;
+
+        if let Some(start_ms) = self.transition_start_ms {
+            let elapsed_ms =
+                u32::try_from(current_time_ms.saturating_sub(start_ms)).unwrap_or(u32::MAX);
+            match transition.progress(elapsed_ms) {
+                Some(progress) => {
+                    let speed = speed_of(speeds, self.previous.color_mode);
+                    self.previous.render(current_time_ms, speed, params, rng);
+                    transition.mix(
+                        &self.previous.leds[..led_qty],
+                        &self.current.leds[..led_qty],
+                        progress,
+                        &mut leds[..led_qty],
+                    );
+                }
+                None => self.transition_start_ms = None,
+            }
+        }
+
+        leds
+    }
+}
+
+/// Registry index of the effect called `name`, or `default` for none or an
+/// unknown name
+fn effect_index(name: Option<&str>, default: u8) -> u8 {
+    let Some(name) = name else {
+        return default;
+    };
+
+    effects::index_of(name).unwrap_or_else(|| {
+        defmt::warn!("Unknown effect {}", name);
+        default
+    })
+}
+
+fn speed_of(speeds: &[u8; MAX_EFFECTS], color_mode: u8) -> u8 {
+    speeds
+        .get(usize::from(color_mode))
+        .copied()
+        .unwrap_or(NORMAL_SPEED)
 }
 
-impl<SPI, TIM, PINS> AdjustablePwmFan<SPI, TIM, PINS>
+impl<OUT, TIM, PINS> AdjustablePwmFan<OUT, TIM, PINS>
 where
-    SPI: spi::SpiBus<u8>,
+    OUT: StripOutput,
     TIM: timer::PwmExt,
     PINS: timer::Pins<TIM>,
 {
@@ -49,6 +275,7 @@ where
             device: pwm_obj,
             channel: pwm_channel,
             current_duty: 25,
+            telemetry: FanTelemetry::default(),
             rgb: None,
         }
     }
@@ -57,11 +284,12 @@ where
         timer: TIM,
         pwm_pin: PINS,
         pwm_channel: timer::Channel,
-        spi_bus: SPI,
+        output: OUT,
+        strip_config: StripConfig,
         clock: &rcc::Clocks,
     ) -> Self {
         let mut new_obj = Self::new(timer, pwm_pin, pwm_channel, clock);
-        new_obj.rgb = Some(PwmFanRgb::new(spi_bus));
+        new_obj.rgb = Some(PwmFanRgb::new(output, strip_config));
 
         new_obj
     }
@@ -88,6 +316,21 @@ where
 
         self.device.set_duty(self.channel, scaled_duty);
         self.current_duty = duty;
+        self.telemetry.duty_percent = duty as u8;
+        self.push_telemetry();
+    }
+
+    /// Report the tachometer reading, `None` if it is not available
+    pub fn set_rpm(&mut self, rpm: Option<u16>) {
+        self.telemetry.rpm = rpm;
+        self.push_telemetry();
+    }
+
+    /// Report the temperature in tenths of a degree Celsius, `None` if it is
+    /// not available
+    pub fn set_temperature(&mut self, temperature_deci_c: Option<i16>) {
+        self.telemetry.temperature_deci_c = temperature_deci_c;
+        self.push_telemetry();
     }
 
     /// Get duty cycle
@@ -103,209 +346,458 @@ where
 
         self.current_duty
     }
+
+    pub fn telemetry(&self) -> FanTelemetry {
+        self.telemetry
+    }
+
+    fn push_telemetry(&mut self) {
+        if let Some(rgb_obj) = &mut self.rgb {
+            rgb_obj.set_fan_telemetry(self.telemetry);
+        }
+    }
 }
 
-impl<SPI> PwmFanRgb<SPI>
+impl<OUT> PwmFanRgb<OUT>
 where
-    SPI: spi::SpiBus<u8>,
+    OUT: StripOutput,
 {
-    pub const MAX_MODES: usize = 13;
-    pub const FAN_LED_QTY: usize = 8;
-    pub const LED_COLOR_PALETTES: [[RGB8; 16]; 4] = [
-        // Forest
-        [
-            colors::DARK_GREEN,
-            colors::DARK_GREEN,
-            colors::DARK_OLIVE_GREEN,
-            colors::DARK_GREEN,
-            colors::GREEN,
-            colors::FOREST_GREEN,
-            colors::OLIVE_DRAB,
-            colors::GREEN,
-            colors::SEA_GREEN,
-            colors::MEDIUM_AQUAMARINE,
-            colors::LIME_GREEN,
-            colors::YELLOW_GREEN,
-            colors::LIGHT_GREEN,
-            colors::LAWN_GREEN,
-            colors::MEDIUM_AQUAMARINE,
-            colors::FOREST_GREEN,
-        ],
-        // Cloud
-        [
-            colors::BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::DARK_BLUE,
-            colors::BLUE,
-            colors::DARK_BLUE,
-            colors::SKY_BLUE,
-            colors::SKY_BLUE,
-            colors::LIGHT_BLUE,
-            colors::WHITE,
-            colors::LIGHT_BLUE,
-            colors::SKY_BLUE,
-        ],
-        // Heat
-        [
-            RGB::new(0, 0, 0),
-            RGB::new(0x33, 0, 0),
-            RGB::new(0x66, 0, 0),
-            RGB::new(0x99, 0, 0),
-            RGB::new(0xCC, 0, 0),
-            RGB::new(0xFF, 0, 0),
-            RGB::new(0xFF, 0x33, 0),
-            RGB::new(0xFF, 0x66, 0),
-            RGB::new(0xFF, 0x99, 0),
-            RGB::new(0xFF, 0xCC, 0),
-            RGB::new(0xFF, 0xFF, 0),
-            RGB::new(0xFF, 0xFF, 0x33),
-            RGB::new(0xFF, 0xFF, 0x66),
-            RGB::new(0xFF, 0xFF, 0x99),
-            RGB::new(0xFF, 0xFF, 0xCC),
-            RGB::new(0xFF, 0xFF, 0xFF),
-        ],
-        // Rainbow
-        [
-            RGB::new(0xFF, 0, 0),
-            RGB::new(0xD5, 0x2A, 0),
-            RGB::new(0xAB, 0x55, 0),
-            RGB::new(0xAB, 0x7F, 0),
-            RGB::new(0xAB, 0xAB, 0),
-            RGB::new(0x56, 0xD5, 0),
-            RGB::new(0, 0xFF, 0),
-            RGB::new(0, 0xD5, 0x2A),
-            RGB::new(0, 0xAB, 0x55),
-            RGB::new(0, 0x56, 0xAA),
-            RGB::new(0, 0, 0xFF),
-            RGB::new(0x2A, 0, 0xD5),
-            RGB::new(0x55, 0, 0xAB),
-            RGB::new(0x7F, 0, 0x81),
-            RGB::new(0xAB, 0, 0x55),
-            RGB::new(0xD5, 0, 0x2B),
-        ],
-    ];
-
-    fn new(spi_bus: SPI) -> Self {
-        let device = ws2812::Ws2812::new(spi_bus);
+    /// Lighting for the strip described by `config`
+    ///
+    /// WS2812 and SK6812 strips need the SPI bus clocked at 3MHz; APA102 and
+    /// SK9822 strips take any clock up to several MHz, SPI mode 0.
+    pub fn new(output: OUT, config: StripConfig) -> Self {
+        let strip_mode = effect_index(config.effect, 0);
+        let mut zones = core::array::from_fn(|_| ZoneLighting::new(Zone::whole(0), strip_mode));
+        let mut zone_qty = 0;
+        let strip_zones = config
+            .zones
+            .iter()
+            .filter_map(|zone| zone.clipped(config.led_qty))
+            .take(MAX_ZONES);
+        for zone in strip_zones {
+            zones[zone_qty] = ZoneLighting::new(zone, effect_index(zone.effect, strip_mode));
+            zone_qty += 1;
+        }
+        if zone_qty == 0 {
+            zones[0] = ZoneLighting::new(Zone::whole(config.led_qty), strip_mode);
+            zone_qty = 1;
+        }
 
         PwmFanRgb {
-            color_mode: 0,
-            brightness: 128u8, // Default brightness
-            device,
+            device: output,
+            config,
+            params: EffectParams::default(),
+            brightness: 96,
+            ambient: 255,
+            power: PowerFade::new(),
+            dither: TemporalDither::new(),
+            speeds: [NORMAL_SPEED; MAX_EFFECTS],
+            zones,
+            zone_qty,
+            transition: Transition::default(),
+            rng: Rng::new(0x2545_F491),
+            corrected: [RGB8::default(); MAX_LEDS],
+            rendered_at_ms: None,
+            direct: [RGB8::default(); MAX_LEDS],
+            before_scenes: None,
         }
     }
 
-    pub fn increment_mode(&mut self, current_time_ms: u32) -> Result<(), crate::error::Error> {
-        self.color_mode += 1;
-        self.color_mode %= u8::try_from(Self::MAX_MODES).unwrap_or(1); // Prevent panic on bad MAX_MODES
-        self.update(current_time_ms)?;
+    pub fn config(&self) -> &StripConfig {
+        &self.config
+    }
+
+    pub fn increment_mode(&mut self, current_time_ms: u64) -> Result<(), crate::error::Error> {
+        let mode_qty = u8::try_from(effects::EFFECTS.len()).unwrap_or(u8::MAX); // Prevent panic on a huge registry
+        let mut color_mode = self.zones[0].current.color_mode;
+
+        // Skip effects left out of the cycle, giving up after one lap
+        for _ in 0..mode_qty {
+            color_mode = (color_mode + 1) % mode_qty;
+            if effects::EFFECTS[usize::from(color_mode)].in_cycle() {
+                break;
+            }
+        }
+
+        self.set_mode(color_mode, current_time_ms)
+    }
+
+    /// Switch every zone to the effect at `color_mode` in `effects::EFFECTS`
+    pub fn set_mode(
+        &mut self,
+        color_mode: u8,
+        current_time_ms: u64,
+    ) -> Result<(), crate::error::Error> {
+        for zone in &mut self.zones[..self.zone_qty] {
+            zone.set_mode(color_mode, current_time_ms);
+        }
+        self.rendered_at_ms = None;
+        self.update(current_time_ms)
+    }
+
+    /// Switch `zone` to the effect at `color_mode`; unknown zones are left
+    /// alone
+    pub fn set_zone_mode(
+        &mut self,
+        zone: usize,
+        color_mode: u8,
+        current_time_ms: u64,
+    ) -> Result<(), crate::error::Error> {
+        if let Some(zone) = self.zones[..self.zone_qty].get_mut(zone) {
+            zone.set_mode(color_mode, current_time_ms);
+            self.rendered_at_ms = None;
+            self.update(current_time_ms)?;
+        }
 
         Ok(())
     }
 
-    pub fn get_mode_text(&self) -> &'static str {
-        match self.color_mode {
-            0 => "Rainbow Twirl",
-            1 => "Rainbow Fade",
-            2 => "Rainbow Palette",
-            3 => "Forest Palette",
-            4 => "Cloud Palette",
-            5 => "Heat Palette",
-            6 => "Red Static",
-            7 => "Green Static",
-            8 => "Blue Static",
-            9 => "White Static",
-            10 => "Yellow Static",
-            11 => "Cyan Static",
-            12 => "Magenta Static",
-            _ => "Unknown Mode",
-        }
-    }
-
-    pub fn update(&mut self, current_time_ms: u32) -> Result<(), crate::error::Error> {
-        let mut leds: [RGB8; Self::FAN_LED_QTY] = [RGB8::default(); Self::FAN_LED_QTY];
-        let time_val = current_time_ms;
-
-        match self.color_mode {
-            // Rainbow Twirl
-            0 => {
-                for i in 0..Self::FAN_LED_QTY {
-                    let hue = ((time_val / 20).wrapping_add((i * 256 / Self::FAN_LED_QTY) as u32)
-                        % 256) as u8;
-                    let hsv_color = Hsv {
-                        hue,
-                        sat: 255,
-                        val: self.brightness,
-                    };
-                    leds[i] = hsv2rgb(hsv_color);
-                }
-            }
-            // Rainbow Fade
-            1 => {
-                let hue = (time_val / 30 % 256) as u8;
-                let hsv_color = Hsv {
-                    hue,
-                    sat: 255,
-                    val: self.brightness,
-                };
-                for i in 0..Self::FAN_LED_QTY {
-                    leds[i] = hsv2rgb(hsv_color);
-                }
-            }
-            // Palette-based modes
-            2 => self.palette_cycler(&mut leds, time_val, 3), // Rainbow Palette
-            3 => self.palette_cycler(&mut leds, time_val, 0), // Forest Palette
-            4 => self.palette_cycler(&mut leds, time_val, 1), // Cloud Palette
-            5 => self.palette_cycler(&mut leds, time_val, 2), // Heat Palette
-            // Static Colors
-            6 => leds = [colors::RED; Self::FAN_LED_QTY],
-            7 => leds = [colors::GREEN; Self::FAN_LED_QTY],
-            8 => leds = [colors::BLUE; Self::FAN_LED_QTY],
-            9 => leds = [colors::WHITE; Self::FAN_LED_QTY],
-            10 => leds = [colors::YELLOW; Self::FAN_LED_QTY],
-            11 => leds = [colors::CYAN; Self::FAN_LED_QTY],
-            12 => leds = [colors::MAGENTA; Self::FAN_LED_QTY],
-
-            _ => {
-                // Default to off or a simple pattern
-                for i in 0..Self::FAN_LED_QTY {
-                    leds[i] = RGB8::default();
-                }
+    /// Zones the strip is split into, in strip order as configured
+    pub fn zones(&self) -> impl Iterator<Item = &Zone> {
+        self.zones[..self.zone_qty].iter().map(|zone| &zone.zone)
+    }
+
+    /// Effect of `zone`, index into `effects::EFFECTS`
+    pub fn zone_mode(&self, zone: usize) -> Option<u8> {
+        self.zones[..self.zone_qty]
+            .get(zone)
+            .map(|zone| zone.current.color_mode)
+    }
+
+    /// Set the brightness of `zone` relative to the strip's, 255 being the
+    /// same
+    pub fn set_zone_brightness(&mut self, zone: usize, brightness: u8) {
+        if let Some(zone) = self.zones[..self.zone_qty].get_mut(zone) {
+            zone.brightness = brightness;
+            self.rendered_at_ms = None;
+        }
+    }
+
+    /// Run the effect of `zone` the other way than configured or back
+    pub fn set_zone_reversed(&mut self, zone: usize, reversed: bool) {
+        if let Some(zone) = self.zones[..self.zone_qty].get_mut(zone) {
+            zone.zone.reversed = reversed;
+            self.rendered_at_ms = None;
+        }
+    }
+
+    /// Switch every zone to direct mode, showing colors set through
+    /// `direct_leds` on the whole strip
+    pub fn set_direct_mode(&mut self, current_time_ms: u64) -> Result<(), crate::error::Error> {
+        if self.is_direct_mode() {
+            return Ok(());
+        }
+
+        let Some(color_mode) = effects::EFFECTS
+            .iter()
+            .position(|effect| effect.is_direct())
+            .and_then(|index| u8::try_from(index).ok())
+        else {
+            return Ok(());
+        };
+        for zone in &mut self.zones[..self.zone_qty] {
+            zone.mode_before_direct = Some(zone.current.color_mode);
+        }
+        self.direct = [RGB8::default(); MAX_LEDS];
+        self.set_mode(color_mode, current_time_ms)
+    }
+
+    /// Go back to the effects shown before `set_direct_mode`
+    pub fn leave_direct_mode(&mut self, current_time_ms: u64) -> Result<(), crate::error::Error> {
+        if !self.is_direct_mode() {
+            return Ok(());
+        }
+
+        for zone in &mut self.zones[..self.zone_qty] {
+            if let Some(color_mode) = zone.mode_before_direct.take() {
+                zone.set_mode(color_mode, current_time_ms);
             }
         }
+        self.rendered_at_ms = None;
+        self.update(current_time_ms)
+    }
+
+    /// Whether every zone is in direct mode
+    pub fn is_direct_mode(&self) -> bool {
+        self.zones[..self.zone_qty]
+            .iter()
+            .all(ZoneLighting::is_direct)
+    }
+
+    /// Colors shown in direct mode, before gamma and brightness, in strip
+    /// order; `None` when another mode is active
+    pub fn direct_leds(&mut self) -> Option<&mut [RGB8]> {
+        if !self.is_direct_mode() {
+            return None;
+        }
+
+        // Shown as soon as it is written, not on the next effect frame
+        self.rendered_at_ms = None;
+        Some(&mut self.direct[..self.config.led_qty])
+    }
+
+    /// Set LEDs from `start` on to `colors`, cutting off what does not fit;
+    /// returns how many were set
+    pub fn set_direct_leds(&mut self, start: usize, colors: &[RGB8]) -> usize {
+        let Some(leds) = self.direct_leds() else {
+            return 0;
+        };
+        let Some(leds) = leds.get_mut(start..) else {
+            return 0;
+        };
+
+        let count = leds.len().min(colors.len());
+        leds[..count].copy_from_slice(&colors[..count]);
+        count
+    }
+
+    /// Set every LED to `color` in direct mode
+    pub fn fill_direct(&mut self, color: RGB8) {
+        if let Some(leds) = self.direct_leds() {
+            leds.fill(color);
+        }
+    }
+
+    /// Blend from `from` on the first LED to `to` on the last in direct mode
+    pub fn fill_direct_gradient(&mut self, from: RGB8, to: RGB8) {
+        if let Some(leds) = self.direct_leds() {
+            direct::fill_gradient(leds, from, to);
+        }
+    }
+
+    pub fn get_mode_text(&self) -> &'static str {
+        if !self.power.on {
+            return "Off";
+        }
+
+        match effects::EFFECTS.get(usize::from(self.zones[0].current.color_mode)) {
+            Some(effect) => effect.name(),
+            None => "Unknown Mode",
+        }
+    }
+
+    /// Set the output brightness, 255 being full
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Scale the brightness for the room light, 255 leaving it as set
+    pub fn set_ambient_level(&mut self, level: u8) {
+        self.ambient = level;
+    }
+
+    /// Fade the LEDs on or off; while off, no frames are rendered or sent
+    pub fn set_on(&mut self, on: bool, current_time_ms: u64) {
+        self.power.set_on(on, current_time_ms);
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.power.on
+    }
+
+    /// Whether the strip has faded out and stopped taking updates
+    pub fn is_dark(&self) -> bool {
+        self.power.dark
+    }
 
-        // Apply gamma correction and brightness
-        let bright_leds: [RGB8; Self::FAN_LED_QTY] = gamma(leds)
+    /// Go to the next of `BRIGHTNESS_STEPS`, wrapping to the dimmest
+    pub fn step_brightness(&mut self) -> u8 {
+        self.brightness = BRIGHTNESS_STEPS
             .iter()
-            .map(|&c| {
-                c.iter()
-                    .map(|cc| (u16::from(cc) * u16::from(self.brightness) / 255) as u8)
-                    .collect()
-            })
-            .collect();
-        self.device
-            .write(bright_leds.iter().cloned())
-            .map_err(|_| crate::error::Error::SpiError)?;
+            .copied()
+            .find(|&step| step > self.brightness)
+            .unwrap_or(BRIGHTNESS_STEPS[0]);
+        self.brightness
+    }
+
+    /// Set the gamma and white balance of the strip
+    pub fn set_correction(&mut self, correction: ColorCorrection) {
+        self.config.correction = correction;
+        self.rendered_at_ms = None;
+    }
+
+    /// Set the animation speed of the first zone's effect, 64 being normal
+    pub fn set_speed(&mut self, speed: u8) {
+        self.set_effect_speed(self.zones[0].current.color_mode, speed);
+    }
+
+    /// Set the animation speed of the effect at `color_mode`
+    ///
+    /// Each effect keeps its own speed, as what looks right differs a lot
+    /// between e.g. a strobe and a slow palette scroll.
+    pub fn set_effect_speed(&mut self, color_mode: u8, speed: u8) {
+        if let Some(slot) = self.speeds.get_mut(usize::from(color_mode)) {
+            *slot = speed;
+        }
+    }
+
+    /// Set the color used by single color effects
+    pub fn set_color(&mut self, color: RGB8) {
+        self.params.color = color;
+    }
+
+    /// Feed the latest fan readings to the reactive effects
+    pub fn set_fan_telemetry(&mut self, fan: FanTelemetry) {
+        self.params.fan = fan;
+    }
+
+    /// Feed the latest microphone analysis to the sound effect
+    pub fn set_audio_levels(&mut self, audio: AudioLevels) {
+        self.params.audio = audio;
+    }
+
+    /// Set the colors and palettes of the "User" effects
+    pub fn set_user_colors(&mut self, user: UserColors) {
+        self.params.user = user;
+    }
+
+    /// Set how mode changes are animated
+    pub fn set_transition(&mut self, transition: Transition) {
+        self.transition = transition;
+    }
+
+    /// Switch to `scene`, bringing it in with its own transition
+    ///
+    /// The transition, color and speed it brings along stay until
+    /// `end_scenes`.
+    pub fn apply_scene(
+        &mut self,
+        scene: &Scene,
+        current_time_ms: u64,
+    ) -> Result<(), crate::error::Error> {
+        if self.before_scenes.is_none() {
+            self.before_scenes = Some(SceneBackup {
+                transition: self.transition,
+                color: self.params.color,
+                speeds: self.speeds,
+            });
+        }
+        self.set_transition(scene.transition);
+        self.set_effect_speed(scene.mode, scene.speed);
+        self.set_color(scene.color);
+        self.set_mode(scene.mode, current_time_ms)
+    }
+
+    /// Go back to the transition, color and speeds from before the first
+    /// `apply_scene`, keeping the effect shown
+    pub fn end_scenes(&mut self) {
+        if let Some(backup) = self.before_scenes.take() {
+            self.transition = backup.transition;
+            self.params.color = backup.color;
+            self.speeds = backup.speeds;
+        }
+    }
+
+    /// Render and send a frame at `current_time_ms` of the lighting clock
+    pub fn update(&mut self, current_time_ms: u64) -> Result<(), crate::error::Error> {
+        if self.power.dark {
+            return Ok(());
+        }
+        let power_level = self.power.level(current_time_ms);
+
+        let led_qty = self.config.led_qty;
+        let render_due = self
+            .rendered_at_ms
+            .is_none_or(|rendered_at| current_time_ms.saturating_sub(rendered_at) >= RENDER_MS);
+        if render_due {
+            self.render(current_time_ms);
+        }
+
+        // Only scaling and encoding run on every update, so dithering can
+        // refresh faster than the effects are drawn
+        let brightness =
+            effects::scale8(effects::scale8(self.brightness, self.ambient), power_level);
+        let mut out_leds = [RGB8::default(); MAX_LEDS];
+        let (corrected_leds, out_leds) = (&self.corrected[..led_qty], &mut out_leds[..led_qty]);
+
+        // Dithering keeps dim colors and fades smooth; it needs `update` to
+        // run about 100 times per second or more not to flicker
+        if brightness > 0 {
+            self.dither.scale(corrected_leds, brightness, out_leds);
+        } else {
+            // Residuals would keep faded out LEDs glowing
+            out_leds.fill(RGB8::default());
+        }
+        let limit_scale = self.config.power_budget.limit(out_leds);
+
+        // The frame is dropped if the previous one is still going out
+        let Some(frame) = self.device.frame_buffer() else {
+            return Ok(());
+        };
+        match self.config.protocol {
+            Protocol::Ws2812 => strip::encode_ws2812_frame(&self.config, out_leds, frame),
+            // Brightness is sent separately, so the colors go out unscaled
+            Protocol::Apa102 => strip::encode_apa102_frame(
+                &self.config,
+                corrected_leds,
+                effects::scale8(brightness, limit_scale),
+                frame,
+            ),
+        }
+        self.device.send()?;
+        self.power.dark = !self.power.on && power_level == 0;
 
         Ok(())
     }
 
-    fn palette_cycler(
-        &self,
-        leds: &mut [RGB8; Self::FAN_LED_QTY],
-        time_val: u32,
-        palette_idx: usize,
-    ) {
-        let palette = Self::LED_COLOR_PALETTES[palette_idx % Self::LED_COLOR_PALETTES.len()];
-        for i in 0..Self::FAN_LED_QTY {
-            let color_idx =
-                ((time_val / 100).wrapping_add(i as u32) % palette.len() as u32) as usize;
-            leds[i] = palette[color_idx];
+    /// Draw every zone into `corrected`
+    fn render(&mut self, current_time_ms: u64) {
+        let led_qty = self.config.led_qty;
+        // Strip order; LEDs outside of every zone stay off, unless the whole
+        // strip is in direct mode
+        let mut leds = if self.is_direct_mode() {
+            self.direct
+        } else {
+            [RGB8::default(); MAX_LEDS]
+        };
+        let mut zone_scales = [255u8; MAX_LEDS];
+
+        for zone in &mut self.zones[..self.zone_qty] {
+            if zone.is_direct() {
+                // Direct colors are in strip order, whichever way the zone runs
+                for (i, led) in zone.current.leds[..zone.current.led_qty]
+                    .iter_mut()
+                    .enumerate()
+                {
+                    *led = self
+                        .direct
+                        .get(zone.zone.led(i))
+                        .copied()
+                        .unwrap_or_default();
+                }
+            }
+            let zone_leds = zone.render(
+                current_time_ms,
+                &self.speeds,
+                &self.transition,
+                &self.params,
+                &mut self.rng,
+            );
+            for (i, &color) in zone_leds[..zone.zone.len].iter().enumerate() {
+                let led = zone.zone.led(i);
+                if led < led_qty {
+                    leds[led] = color;
+                    zone_scales[led] = zone.brightness;
+                }
+            }
+        }
+
+        // Effects draw at full value; gamma and white balance, then zone,
+        // strip, ambient and on/off brightness, then the power limit are
+        // applied here and in `update`, and nowhere else
+        for ((corrected_led, &led), &zone_scale) in self
+            .corrected
+            .iter_mut()
+            .zip(&leds[..led_qty])
+            .zip(&zone_scales)
+        {
+            *corrected_led = effects::scale_color(self.config.correction.apply(led), zone_scale);
         }
+        self.rendered_at_ms = Some(current_time_ms);
     }
 }
@@ -0,0 +1,201 @@
+use smart_leds::{RGB8, colors};
+
+use crate::effects::{self, NORMAL_SPEED, transition::Transition};
+
+/// Most scenes a scene list holds
+pub const MAX_SCENES: usize = 16;
+/// Time each effect is shown in the demo playlist
+pub const DEMO_SCENE_MS: u32 = 8000;
+/// Shortest time a scene is shown, so a playlist cannot switch every frame
+pub const MIN_SCENE_MS: u32 = 100;
+
+/// One step of a playlist: an effect with its settings, shown for a while
+#[derive(Clone, Copy)]
+pub struct Scene {
+    /// Index into `effects::EFFECTS`
+    pub mode: u8,
+    pub speed: u8,
+    pub color: RGB8,
+    pub duration_ms: u32,
+    /// How this scene comes in
+    pub transition: Transition,
+}
+
+impl Scene {
+    pub fn new(mode: u8, duration_ms: u32) -> Self {
+        Self {
+            mode,
+            speed: NORMAL_SPEED,
+            color: colors::DODGER_BLUE,
+            duration_ms,
+            transition: Transition::default(),
+        }
+    }
+}
+
+/// Scenes in play order
+#[derive(Clone, Copy)]
+pub struct SceneList {
+    scenes: [Scene; MAX_SCENES],
+    len: usize,
+}
+
+impl SceneList {
+    pub fn new() -> Self {
+        Self {
+            scenes: [Scene::new(0, 0); MAX_SCENES],
+            len: 0,
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Scene] {
+        &self.scenes[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add `scene` at the end; `false` if the list is full
+    pub fn push(&mut self, scene: Scene) -> bool {
+        match self.scenes.get_mut(self.len) {
+            Some(slot) => {
+                *slot = scene;
+                self.len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the scene at `index`; `false` if there is none
+    pub fn set(&mut self, index: usize, scene: Scene) -> bool {
+        match self.scenes[..self.len].get_mut(index) {
+            Some(slot) => {
+                *slot = scene;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the scene at `index`, moving the later ones up
+    pub fn remove(&mut self, index: usize) -> Option<Scene> {
+        if index >= self.len {
+            return None;
+        }
+
+        let scene = self.scenes[index];
+        self.scenes.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+        Some(scene)
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for SceneList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What the sequencer plays
+#[derive(Clone, Copy, PartialEq)]
+pub enum Playlist {
+    /// The configured scene list
+    Scenes,
+    /// Every effect the button cycles through, one after the other
+    Demo,
+}
+
+/// Steps through a playlist in a loop
+pub struct Sequencer {
+    playing: Option<Playlist>,
+    index: usize,
+    scene_end_ms: u64,
+}
+
+impl Sequencer {
+    pub const fn new() -> Self {
+        Self {
+            playing: None,
+            index: 0,
+            scene_end_ms: 0,
+        }
+    }
+
+    /// Start `playlist` from its first scene on the next `tick`
+    pub fn start(&mut self, playlist: Playlist) {
+        self.playing = Some(playlist);
+        self.index = usize::MAX; // Wraps to the first scene
+        self.scene_end_ms = 0;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = None;
+    }
+
+    pub fn playing(&self) -> Option<Playlist> {
+        self.playing
+    }
+
+    /// The scene to switch to at `current_time_ms`, if it is time for one
+    ///
+    /// A playlist without scenes stops the sequencer.
+    pub fn tick(&mut self, current_time_ms: u64, scenes: &SceneList) -> Option<Scene> {
+        let playlist = self.playing?;
+        if current_time_ms < self.scene_end_ms {
+            return None;
+        }
+
+        let scene = match playlist {
+            Playlist::Scenes => self.next_scene(scenes),
+            Playlist::Demo => self.next_demo_scene(),
+        };
+        match scene {
+            Some(scene) => {
+                self.scene_end_ms =
+                    current_time_ms + u64::from(scene.duration_ms.max(MIN_SCENE_MS));
+            }
+            None => self.stop(),
+        }
+
+        scene
+    }
+
+    fn next_scene(&mut self, scenes: &SceneList) -> Option<Scene> {
+        if scenes.is_empty() {
+            return None;
+        }
+
+        self.index = self.index.wrapping_add(1) % scenes.len();
+        Some(scenes.as_slice()[self.index])
+    }
+
+    fn next_demo_scene(&mut self) -> Option<Scene> {
+        let mode_qty = effects::EFFECTS.len().min(usize::from(u8::MAX));
+
+        // At most one lap looking for an effect that is in the button cycle
+        for _ in 0..mode_qty {
+            self.index = self.index.wrapping_add(1) % mode_qty;
+            if effects::EFFECTS[self.index].in_cycle() {
+                return Some(Scene::new(self.index as u8, DEMO_SCENE_MS));
+            }
+        }
+
+        None
+    }
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,304 @@
+use smart_leds::RGB8;
+
+use crate::correction::ColorCorrection;
+use crate::error::Error;
+use crate::power::PowerBudget;
+use crate::zone::Zone;
+
+/// Most LEDs one strip can be configured with
+pub const MAX_LEDS: usize = 32;
+/// Low time that latches a WS2812 frame, in SPI bytes at 3MHz (~370us)
+pub const WS2812_RESET_BYTES: usize = 140;
+
+/// Zero bytes that start an APA102 frame
+pub const APA102_START_BYTES: usize = 4;
+/// Size of an encoded frame: the largest, an RGBW WS2812 strip of
+/// `MAX_LEDS` between two resets, fits
+pub const FRAME_BYTES: usize = 2 * WS2812_RESET_BYTES + MAX_LEDS * 4 * 4;
+
+/// Encoded frame, zero padded; trailing zeros are harmless to both
+/// protocols, so it is always sent whole
+pub type FrameBuffer = [u8; FRAME_BYTES];
+
+// Two data bits per SPI byte, each as 4 SPI bits: 0b1000 for 0, 0b1110 for 1
+const WS2812_PATTERNS: [u8; 4] = [0b1000_1000, 0b1000_1110, 0b1110_1000, 0b1110_1110];
+
+/// Order the strip expects the color bytes in
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ColorOrder {
+    pub fn arrange(self, color: RGB8) -> [u8; 3] {
+        let RGB8 { r, g, b } = color;
+        match self {
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Rbg => [r, b, g],
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Gbr => [g, b, r],
+            ColorOrder::Brg => [b, r, g],
+            ColorOrder::Bgr => [b, g, r],
+        }
+    }
+}
+
+/// How the strip is driven
+#[derive(Clone, Copy, PartialEq)]
+pub enum Protocol {
+    /// WS2812 / SK6812 timing encoded on MOSI, SPI at 3MHz
+    Ws2812,
+    /// APA102 / SK9822 with clock and data, brightness in the 5-bit global field
+    Apa102,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PixelKind {
+    Rgb,
+    /// Extra white LED (SK6812 RGBW), sent after the colors
+    Rgbw,
+}
+
+/// Layout of one LED strip
+#[derive(Clone, Copy)]
+pub struct StripConfig {
+    /// Number of LEDs, at most `MAX_LEDS`
+    pub led_qty: usize,
+    pub protocol: Protocol,
+    pub color_order: ColorOrder,
+    pub pixel: PixelKind,
+    /// Name of the effect shown after power up; `None` for the first one
+    pub effect: Option<&'static str>,
+    /// Parts of the strip with effects of their own; empty for one effect
+    /// on the whole strip
+    pub zones: &'static [Zone],
+    pub correction: ColorCorrection,
+    /// Current the LEDs are kept under
+    pub power_budget: PowerBudget,
+}
+
+impl StripConfig {
+    /// WS2812 strip of `led_qty` LEDs
+    pub const fn new(led_qty: usize) -> Self {
+        Self {
+            led_qty: if led_qty > MAX_LEDS {
+                MAX_LEDS
+            } else {
+                led_qty
+            },
+            protocol: Protocol::Ws2812,
+            color_order: ColorOrder::Grb,
+            pixel: PixelKind::Rgb,
+            effect: None,
+            zones: &[],
+            correction: ColorCorrection::new(),
+            power_budget: PowerBudget::DEFAULT,
+        }
+    }
+
+    /// SK6812 RGBW strip of `led_qty` LEDs
+    pub const fn rgbw(led_qty: usize) -> Self {
+        let mut config = Self::new(led_qty);
+        config.pixel = PixelKind::Rgbw;
+        config
+    }
+
+    /// APA102 or SK9822 strip of `led_qty` LEDs
+    pub const fn apa102(led_qty: usize) -> Self {
+        let mut config = Self::new(led_qty);
+        config.protocol = Protocol::Apa102;
+        config.color_order = ColorOrder::Bgr;
+        config
+    }
+
+    pub const fn with_color_order(mut self, color_order: ColorOrder) -> Self {
+        self.color_order = color_order;
+        self
+    }
+
+    pub const fn with_effect(mut self, name: &'static str) -> Self {
+        self.effect = Some(name);
+        self
+    }
+
+    pub const fn with_correction(mut self, correction: ColorCorrection) -> Self {
+        self.correction = correction;
+        self
+    }
+
+    /// Keep the LEDs under `budget`, e.g. what their supply can deliver
+    pub const fn with_power_budget(mut self, budget: PowerBudget) -> Self {
+        self.power_budget = budget;
+        self
+    }
+
+    /// Split the strip into `zones`; only the first `MAX_ZONES` are used
+    pub const fn with_zones(mut self, zones: &'static [Zone]) -> Self {
+        self.zones = zones;
+        self
+    }
+
+    /// Bytes of one LED in the order they are sent, and how many are used
+    pub fn pixel_bytes(&self, color: RGB8) -> ([u8; 4], usize) {
+        match self.pixel {
+            PixelKind::Rgb => {
+                let [a, b, c] = self.color_order.arrange(color);
+                ([a, b, c, 0], 3)
+            }
+            PixelKind::Rgbw => {
+                let (color, white) = split_white(color);
+                let [a, b, c] = self.color_order.arrange(color);
+                ([a, b, c, white], 4)
+            }
+        }
+    }
+}
+
+impl Default for StripConfig {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+/// Move the part all three channels share to the white LED
+pub fn split_white(color: RGB8) -> (RGB8, u8) {
+    let white = color.r.min(color.g).min(color.b);
+    (
+        RGB8::new(color.r - white, color.g - white, color.b - white),
+        white,
+    )
+}
+
+/// SPI bytes that send one data byte to a WS2812 at 3MHz, MSB first
+pub fn encode_ws2812(byte: u8) -> [u8; 4] {
+    let pattern = |shift: u8| WS2812_PATTERNS[usize::from((byte >> shift) & 0b11)];
+    [pattern(6), pattern(4), pattern(2), pattern(0)]
+}
+
+/// One APA102 LED frame for `color` (already in wire order) at `brightness`
+///
+/// Brightness goes into the 5-bit global field, rounded up, and the colors
+/// make up the rest. Dim settings keep the full 8 bits of color resolution
+/// this way instead of losing them to the scaling.
+pub fn encode_apa102(color: [u8; 3], brightness: u8) -> [u8; 4] {
+    let global = (u16::from(brightness) * 31).div_ceil(255);
+    if global == 0 {
+        return [0b1110_0000, 0, 0, 0];
+    }
+
+    // brightness / 255 = global / 31 * scale / 255
+    let scale = |value: u8| {
+        (u32::from(value) * u32::from(brightness) * 31 / (u32::from(global) * 255)) as u8
+    };
+    let [a, b, c] = color;
+
+    [0b1110_0000 | global as u8, scale(a), scale(b), scale(c)]
+}
+
+/// Encode a WS2812 frame of `leds` into `out`
+///
+/// There is a reset both before and after the LEDs, as MOSI idles high
+/// between frames.
+pub fn encode_ws2812_frame(config: &StripConfig, leds: &[RGB8], out: &mut FrameBuffer) {
+    out.fill(0);
+
+    let mut pos = WS2812_RESET_BYTES;
+    for &led in leds.iter().take(MAX_LEDS) {
+        let (bytes, len) = config.pixel_bytes(led);
+        for &byte in &bytes[..len] {
+            out[pos..pos + 4].copy_from_slice(&encode_ws2812(byte));
+            pos += 4;
+        }
+    }
+}
+
+/// Encode an APA102 frame of `leds` at `brightness` into `out`
+pub fn encode_apa102_frame(
+    config: &StripConfig,
+    leds: &[RGB8],
+    brightness: u8,
+    out: &mut FrameBuffer,
+) {
+    out.fill(0);
+
+    // Start and end frames are all zeros, already there from the fill. The
+    // padding covers both the 32 bit reset frame an SK9822 latches on and
+    // the half clock per LED the data needs to reach the end of the strip.
+    let mut pos = APA102_START_BYTES;
+    for &led in leds.iter().take(MAX_LEDS) {
+        let color = config.color_order.arrange(led);
+        out[pos..pos + 4].copy_from_slice(&encode_apa102(color, brightness));
+        pos += 4;
+    }
+}
+
+/// Where encoded frames go out
+pub trait StripOutput {
+    /// Buffer to encode the next frame into, `None` if there is none free
+    fn frame_buffer(&mut self) -> Option<&mut FrameBuffer>;
+
+    /// Start sending the frame in `frame_buffer`
+    ///
+    /// Returns `false` if the previous frame is still going out, in which
+    /// case the new one is dropped.
+    fn send(&mut self) -> Result<bool, Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apa102_full_brightness_keeps_colors() {
+        assert_eq!(encode_apa102([1, 128, 255], 255), [0xFF, 1, 128, 255]);
+    }
+
+    #[test]
+    fn apa102_zero_brightness_is_off() {
+        assert_eq!(encode_apa102([255, 255, 255], 0), [0b1110_0000, 0, 0, 0]);
+    }
+
+    #[test]
+    fn apa102_dim_keeps_color_resolution() {
+        let [header, a, b, c] = encode_apa102([255, 100, 3], 8);
+
+        assert_eq!(header, 0b1110_0000 | 1);
+        assert_eq!([a, b, c], [248, 97, 2]);
+    }
+
+    #[test]
+    fn apa102_light_output_follows_brightness() {
+        for brightness in [1u8, 7, 8, 9, 31, 100, 200, 254] {
+            for value in [1u8, 50, 255] {
+                let [header, out, _, _] = encode_apa102([value, 0, 0], brightness);
+                let global = u32::from(header & 0x1F);
+                // Light out of 255 * 255 * 31, against what was asked for
+                let light = global * u32::from(out) * 255;
+                let wanted = u32::from(value) * u32::from(brightness) * 31;
+                assert!(light <= wanted, "{brightness} {value}");
+                assert!(wanted - light < global * 255, "{brightness} {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn apa102_frame_layout() {
+        let config = StripConfig::apa102(2);
+        let mut frame = [0xAA; FRAME_BYTES];
+
+        encode_apa102_frame(
+            &config,
+            &[RGB8::new(1, 2, 3), RGB8::new(4, 5, 6)],
+            255,
+            &mut frame,
+        );
+        assert_eq!(frame[..APA102_START_BYTES], [0; APA102_START_BYTES]);
+        assert_eq!(frame[4..12], [0xFF, 3, 2, 1, 0xFF, 6, 5, 4]); // BGR
+        assert!(frame[12..].iter().all(|&byte| byte == 0));
+    }
+}
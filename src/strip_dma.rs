@@ -0,0 +1,92 @@
+use stm32f4xx_hal::{
+    dma::{
+        ChannelX, DMAError, MemoryToPeripheral, Transfer,
+        config::DmaConfig,
+        traits::{Channel, DMASet, Stream, StreamISR},
+    },
+    spi::{Instance, Tx},
+};
+
+use crate::error::Error;
+use crate::strip::{FrameBuffer, StripOutput};
+
+/// Strip output fed by SPI TX DMA, double buffered
+///
+/// One buffer is being sent while the next frame is encoded into the other,
+/// so sending never blocks and frames go out with steady timing.
+pub struct DmaStrip<STREAM, const CH: u8, SPI>
+where
+    STREAM: Stream + StreamISR,
+    ChannelX<CH>: Channel,
+    SPI: Instance,
+    Tx<SPI>: DMASet<STREAM, CH, MemoryToPeripheral>,
+{
+    transfer: Transfer<STREAM, CH, Tx<SPI>, MemoryToPeripheral, &'static mut FrameBuffer>,
+    spare: Option<&'static mut FrameBuffer>, // Not in use by the DMA
+}
+
+impl<STREAM, const CH: u8, SPI> DmaStrip<STREAM, CH, SPI>
+where
+    STREAM: Stream + StreamISR,
+    ChannelX<CH>: Channel,
+    SPI: Instance,
+    Tx<SPI>: DMASet<STREAM, CH, MemoryToPeripheral>,
+{
+    /// Start sending `first` (normally all zeros, a reset) and keep
+    /// `second` for the next frame
+    pub fn new(
+        stream: STREAM,
+        tx: Tx<SPI>,
+        first: &'static mut FrameBuffer,
+        second: &'static mut FrameBuffer,
+    ) -> Self {
+        let mut transfer = Transfer::init_memory_to_peripheral(
+            stream,
+            tx,
+            first,
+            None,
+            DmaConfig::default()
+                .memory_increment(true)
+                .fifo_enable(true),
+        );
+        transfer.start(|_tx| {});
+
+        Self {
+            transfer,
+            spare: Some(second),
+        }
+    }
+}
+
+impl<STREAM, const CH: u8, SPI> StripOutput for DmaStrip<STREAM, CH, SPI>
+where
+    STREAM: Stream + StreamISR,
+    ChannelX<CH>: Channel,
+    SPI: Instance,
+    Tx<SPI>: DMASet<STREAM, CH, MemoryToPeripheral>,
+{
+    fn frame_buffer(&mut self) -> Option<&mut FrameBuffer> {
+        self.spare.as_deref_mut()
+    }
+
+    fn send(&mut self) -> Result<bool, Error> {
+        let Some(frame) = self.spare.take() else {
+            return Err(Error::SPI);
+        };
+
+        // Swaps buffers once the stream has stopped, handing back the one
+        // that was just sent
+        match self.transfer.next_transfer(frame) {
+            Ok((sent, _)) => {
+                self.spare = Some(sent);
+                Ok(true)
+            }
+            Err(DMAError::NotReady(frame)) => {
+                self.spare = Some(frame);
+                Ok(false)
+            }
+            // Only returned in double buffer mode, which is not used
+            Err(_) => Err(Error::SPI),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use core::fmt::{self, Write};
+
+/// Fixed capacity string for composing text without a heap
+///
+/// Writes that do not fit are cut at the last whole character and reported
+/// as `fmt::Error`, so whatever fitted is still available from `as_str`.
+pub struct TextBuf<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TextBuf<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0u8; N],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+impl<const N: usize> Default for TextBuf<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Write for TextBuf<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let free = N - self.len;
+        let mut take = s.len().min(free);
+        while !s.is_char_boundary(take) {
+            take -= 1;
+        }
+
+        self.buf[self.len..self.len + take].copy_from_slice(&s.as_bytes()[..take]);
+        self.len += take;
+
+        if take < s.len() {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Fixed-point decimal, e.g. `Fixed::new(235, 1)` prints as `23.5`
+///
+/// Width, fill and alignment from the format string apply to the whole
+/// number, so `"{:>5}C"` gives `" 23.5C"`.
+#[derive(Clone, Copy)]
+pub struct Fixed {
+    value: i32,
+    decimals: u8,
+}
+
+impl Fixed {
+    pub const MAX_DECIMALS: u8 = 9;
+
+    /// `value` is in units of 10^-`decimals`
+    pub fn new(value: i32, decimals: u8) -> Self {
+        Self {
+            value,
+            decimals: decimals.min(Self::MAX_DECIMALS),
+        }
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = TextBuf::<16>::new();
+        let scale = 10u32.pow(u32::from(self.decimals));
+        let magnitude = self.value.unsigned_abs();
+
+        // Sign is written by hand so that e.g. -0.5 keeps its minus
+        if self.value < 0 {
+            text.write_char('-')?;
+        }
+        write!(text, "{}", magnitude / scale)?;
+        if self.decimals > 0 {
+            write!(
+                text,
+                ".{:0width$}",
+                magnitude % scale,
+                width = usize::from(self.decimals)
+            )?;
+        }
+
+        f.pad(text.as_str())
+    }
+}
+
+/// Fan speed shortened with a thousands suffix
+///
+/// Prints `950`, `1.2k` and `12k`, so it never needs more than four columns
+/// below 100 000 RPM. Digits are truncated, not rounded.
+#[derive(Clone, Copy)]
+pub struct Rpm(pub u32);
+
+impl fmt::Display for Rpm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = TextBuf::<12>::new();
+
+        match self.0 {
+            0..1_000 => write!(text, "{}", self.0)?,
+            1_000..10_000 => write!(text, "{}.{}k", self.0 / 1_000, self.0 / 100 % 10)?,
+            _ => write!(text, "{}k", self.0 / 1_000)?,
+        }
+
+        f.pad(text.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(args: fmt::Arguments) -> TextBuf<32> {
+        let mut text = TextBuf::new();
+        text.write_fmt(args).unwrap();
+        text
+    }
+
+    #[test]
+    fn text_buf_cuts_at_char_boundary() {
+        let mut text = TextBuf::<4>::new();
+        assert!(write!(text, "ab\u{e9}\u{e9}").is_err());
+        assert_eq!(text.as_str(), "ab\u{e9}");
+    }
+
+    #[test]
+    fn fixed_prints_decimals() {
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(235, 1))).as_str(),
+            "23.5"
+        );
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(7, 2))).as_str(),
+            "0.07"
+        );
+        assert_eq!(format(format_args!("{}", Fixed::new(42, 0))).as_str(), "42");
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(-5, 1))).as_str(),
+            "-0.5"
+        );
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(-1234, 2))).as_str(),
+            "-12.34"
+        );
+    }
+
+    #[test]
+    fn fixed_pads_whole_number() {
+        assert_eq!(
+            format(format_args!("{:>5}C", Fixed::new(235, 1))).as_str(),
+            " 23.5C"
+        );
+        assert_eq!(
+            format(format_args!("{:<6}|", Fixed::new(-5, 1))).as_str(),
+            "-0.5  |"
+        );
+    }
+
+    #[test]
+    fn fixed_extremes() {
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(i32::MIN, 9))).as_str(),
+            "-2.147483648"
+        );
+        assert_eq!(
+            format(format_args!("{}", Fixed::new(1, 12))).as_str(),
+            "0.000000001"
+        );
+    }
+
+    #[test]
+    fn rpm_uses_thousands_suffix() {
+        assert_eq!(format(format_args!("{}", Rpm(0))).as_str(), "0");
+        assert_eq!(format(format_args!("{}", Rpm(950))).as_str(), "950");
+        assert_eq!(format(format_args!("{}", Rpm(1_000))).as_str(), "1.0k");
+        assert_eq!(format(format_args!("{}", Rpm(1_299))).as_str(), "1.2k");
+        assert_eq!(format(format_args!("{}", Rpm(9_999))).as_str(), "9.9k");
+        assert_eq!(format(format_args!("{}", Rpm(12_345))).as_str(), "12k");
+        assert_eq!(format(format_args!("{:>5}", Rpm(950))).as_str(), "  950");
+    }
+}
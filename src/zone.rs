@@ -0,0 +1,72 @@
+/// Most zones one strip can be split into
+pub const MAX_ZONES: usize = 4;
+
+/// Run of LEDs on a strip showing its own effect
+///
+/// Effects draw a zone from its LED 0 on. `offset` turns the effect along
+/// the run, e.g. to line it up with the top of a ring, and `reversed`
+/// mirrors it, so it runs from the last LED to the first.
+#[derive(Clone, Copy)]
+pub struct Zone {
+    pub name: &'static str,
+    /// First LED on the strip
+    pub start: usize,
+    pub len: usize,
+    pub reversed: bool,
+    /// LEDs the effect is moved along by, wrapping within the run
+    pub offset: usize,
+    /// Name of the effect shown after power up; `None` for the strip's
+    pub effect: Option<&'static str>,
+}
+
+impl Zone {
+    pub const fn new(name: &'static str, start: usize, len: usize) -> Self {
+        Self {
+            name,
+            start,
+            len,
+            reversed: false,
+            offset: 0,
+            effect: None,
+        }
+    }
+
+    /// The one zone of a strip that is not split
+    pub const fn whole(led_qty: usize) -> Self {
+        Self::new("All", 0, led_qty)
+    }
+
+    pub const fn reversed(mut self) -> Self {
+        self.reversed = true;
+        self
+    }
+
+    pub const fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub const fn with_effect(mut self, name: &'static str) -> Self {
+        self.effect = Some(name);
+        self
+    }
+
+    /// This zone cut down to a strip of `led_qty` LEDs; `None` if nothing
+    /// of it is left
+    pub fn clipped(mut self, led_qty: usize) -> Option<Self> {
+        self.len = self.len.min(led_qty.saturating_sub(self.start));
+        (self.len > 0).then_some(self)
+    }
+
+    /// Strip index of the zone's LED `index`, as its effect counts them
+    pub fn led(&self, index: usize) -> usize {
+        let len = self.len.max(1);
+        let index = (index + self.offset) % len;
+
+        if self.reversed {
+            self.start + len - 1 - index
+        } else {
+            self.start + index
+        }
+    }
+}